@@ -0,0 +1,234 @@
+use anyhow::Result;
+#[cfg(feature = "native")]
+use anyhow::anyhow;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read};
+#[cfg(feature = "native")]
+use std::fs::{self, File};
+#[cfg(feature = "native")]
+use std::path::Component;
+use std::path::{Path, PathBuf};
+
+/// Destination for the physical asset files extracted from a publication
+pub trait AssetSink {
+    /// Stores one asset. `name` is the final file name, already unique within the publication.
+    /// It may contain `/`-separated directories (see `AssetNaming::ContentAddressed`).
+    fn write_asset(&mut self, name: &str, reader: &mut dyn Read) -> Result<()>;
+
+    /// Location reported back to the caller for an asset written under `name`
+    fn location(&self, name: &str) -> PathBuf {
+        PathBuf::from(name)
+    }
+}
+
+/// Default sink: writes every asset as a file inside a directory
+#[cfg(feature = "native")]
+pub struct FsAssetSink {
+    dir: PathBuf,
+}
+
+#[cfg(feature = "native")]
+impl FsAssetSink {
+    /// Creates the directory if needed. Locations are reported as absolute paths.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let dir = fs::canonicalize(&dir)?;
+        Ok(Self { dir })
+    }
+}
+
+#[cfg(feature = "native")]
+impl AssetSink for FsAssetSink {
+    fn write_asset(&mut self, name: &str, reader: &mut dyn Read) -> Result<()> {
+        // Names come from the archive, so anything that could leave the directory is refused
+        let relative = Path::new(name);
+        if name.is_empty() || !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+            return Err(anyhow!("Refusing to write asset '{}' outside {:?}", name, self.dir));
+        }
+
+        let path = self.dir.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut out_file = File::create(path)?;
+        std::io::copy(reader, &mut out_file)?;
+        Ok(())
+    }
+
+    fn location(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+}
+
+/// An asset held in memory as `(file_name, bytes)`
+pub type InMemoryAsset = (String, Vec<u8>);
+
+/// Keeps every asset in memory
+#[derive(Debug, Default)]
+pub struct MemoryAssetSink {
+    pub assets: Vec<InMemoryAsset>,
+}
+
+impl AssetSink for MemoryAssetSink {
+    fn write_asset(&mut self, name: &str, reader: &mut dyn Read) -> Result<()> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        self.assets.push((name.to_string(), bytes));
+        Ok(())
+    }
+}
+
+/// How extracted assets are named under `assets/`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "native", derive(clap::ValueEnum))]
+pub enum AssetNaming {
+    /// The file name from the archive (`202025001_univ_cnt_1.jpg`)
+    #[default]
+    Original,
+    /// `<h0h1>/<h2h3>/<sha256>.<ext>` after the SHA-256 of the content, so the same image
+    /// recurring across publications is stored once when they share an assets directory
+    ContentAddressed,
+}
+
+/// Wraps a sink, writing every asset under its content-addressed path instead of its name.
+/// Identical contents are written once; `names` maps each requested name to its path.
+pub struct ContentAddressedSink<'a> {
+    inner: &'a mut dyn AssetSink,
+    names: HashMap<String, String>,
+    written: HashSet<String>,
+}
+
+impl<'a> ContentAddressedSink<'a> {
+    pub fn new(inner: &'a mut dyn AssetSink) -> Self {
+        Self { inner, names: HashMap::new(), written: HashSet::new() }
+    }
+
+    /// Content-addressed path of every asset written so far, by the name it was given
+    pub fn into_names(self) -> HashMap<String, String> {
+        self.names
+    }
+}
+
+impl AssetSink for ContentAddressedSink<'_> {
+    fn write_asset(&mut self, name: &str, reader: &mut dyn Read) -> Result<()> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        let hash = hex::encode(Sha256::digest(&bytes));
+        let path = match Path::new(name).extension().and_then(|e| e.to_str()) {
+            Some(extension) => format!("{}/{}/{}.{}", &hash[..2], &hash[2..4], hash, extension.to_lowercase()),
+            None => format!("{}/{}/{}", &hash[..2], &hash[2..4], hash),
+        };
+        if self.written.insert(path.clone()) {
+            self.inner.write_asset(&path, &mut bytes.as_slice())?;
+        }
+        self.names.insert(name.to_string(), path);
+        Ok(())
+    }
+
+    fn location(&self, name: &str) -> PathBuf {
+        self.inner.location(self.names.get(name).map_or(name, String::as_str))
+    }
+}
+
+/// Dimensions and format of an image, read from its header
+#[derive(Debug, Clone)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    pub mime: &'static str,
+}
+
+/// Reads the dimensions of an image without decoding its pixels.
+/// Returns `None` for unknown formats or truncated headers.
+pub fn probe_image(bytes: &[u8]) -> Option<ImageInfo> {
+    let reader = image::ImageReader::new(Cursor::new(bytes)).with_guessed_format().ok()?;
+    let mime = reader.format()?.to_mime_type();
+    let (width, height) = reader.into_dimensions().ok()?;
+    Some(ImageInfo { width, height, mime })
+}
+
+/// Guesses the MIME type of an asset from its file extension
+pub fn mime_type_for(file_name: &str) -> &'static str {
+    let ext = Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+
+    match ext.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Detects the image format from its magic bytes, returning the matching file extension
+pub fn sniff_image_extension(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0xFF, 0xD8, 0xFF, ..] => Some("jpg"),
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some("png"),
+        [b'G', b'I', b'F', b'8', ..] => Some("gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("webp"),
+        _ => None,
+    }
+}
+
+/// Replaces the extension of `file_name` with the one matching the sniffed format of `bytes`.
+/// Unknown formats and extensions that already match (`.jpeg` counts as `.jpg`) are kept.
+pub fn correct_extension(file_name: &str, bytes: &[u8]) -> String {
+    let Some(sniffed) = sniff_image_extension(bytes) else {
+        return file_name.to_string();
+    };
+
+    let path = Path::new(file_name);
+    let current = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+    match current.as_deref() {
+        Some("jpeg") if sniffed == "jpg" => file_name.to_string(),
+        Some(current) if current == sniffed => file_name.to_string(),
+        _ => {
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(file_name);
+            format!("{}.{}", stem, sniffed)
+        }
+    }
+}
+
+/// Keeps letters, digits, `-`, `_` and `.`; anything else becomes `_`
+pub fn sanitize_path_component(value: &str) -> String {
+    let sanitized: String = value
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect();
+    sanitized.trim_matches('.').to_string()
+}
+
+/// Returns a file name that has not been used yet, appending `-2`, `-3`, ... to the stem
+/// when assets from different folders of the archive share the same name.
+pub fn unique_file_name(file_name: &str, used: &mut HashSet<String>) -> String {
+    if used.insert(file_name.to_string()) {
+        return file_name.to_string();
+    }
+
+    let path = Path::new(file_name);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(file_name);
+    let ext = path.extension().and_then(|e| e.to_str());
+
+    let mut counter = 2;
+    loop {
+        let candidate = match ext {
+            Some(ext) => format!("{}-{}.{}", stem, counter, ext),
+            None => format!("{}-{}", stem, counter),
+        };
+        if used.insert(candidate.clone()) {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
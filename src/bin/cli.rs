@@ -1,45 +1,319 @@
-use clap::Parser;
-use jw_parser::parse_jwpub;
-use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use jw_parser::models::{Manifest, ParseProgress, ParseStage};
+use jw_parser::assets::{sanitize_path_component, AssetNaming, AssetSink, FsAssetSink, MemoryAssetSink};
+use jw_parser::crypto::EncryptionMode;
+#[cfg(feature = "discovery")]
+use jw_parser::discovery::{DiscoveryService, DownloadConfig, FileFormat};
+use jw_parser::export::{write_references_json, write_standalone_html, ExportFormat};
+use jw_parser::{dump_database, parse_jwpub_dir, parse_jwpub_ex, parse_jwpub_with_sink, ParseOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::fs;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
-struct Args {
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Without a subcommand the arguments are those of `parse`
+    #[command(flatten)]
+    parse: ParseArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Parse a local .jwpub file or directory
+    Parse(ParseArgs),
+    /// Download a publication from the CDN without parsing it
+    #[cfg(feature = "discovery")]
+    Download(DownloadArgs),
+    /// Download a publication and parse it in one step
+    #[cfg(feature = "discovery")]
+    Fetch(FetchArgs),
+}
+
+#[derive(Args, Debug)]
+struct ParseArgs {
     /// Path to the .jwpub file
-    #[arg(short, long)]
-    input: PathBuf,
+    #[arg(short, long, required_unless_present = "input_dir")]
+    input: Option<PathBuf>,
+
+    /// Process every .jwpub in this directory, one subdirectory of the output per publication
+    #[arg(long, conflicts_with_all = ["input", "stdout", "export", "output_template", "debug_db"])]
+    input_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+/// Where and how a parsed publication is written, shared by `parse` and `fetch`
+#[derive(Args, Debug)]
+struct OutputArgs {
+    /// Output directory. Optional with `--stdout`, where it only receives the assets.
+    #[arg(short, long, visible_alias = "output-dir", required_unless_present = "stdout")]
+    output: Option<PathBuf>,
+
+    /// Subdirectory of the output built from the publication metadata, e.g.
+    /// "{symbol}/{year}/{issue}". Placeholders: {symbol}, {year}, {issue}, {language}, {title}
+    #[arg(long, requires = "output", conflicts_with = "stdout")]
+    output_template: Option<String>,
+
+    /// Force the document class to extract instead of looking it up by publication type
+    #[arg(long)]
+    class_id: Option<i32>,
+
+    /// Extract every document class in the publication
+    #[arg(long, conflicts_with = "class_id")]
+    all_classes: bool,
+
+    /// Keep going when a document fails to decrypt, listing it under "errors" in the manifest
+    #[arg(long)]
+    skip_failed: bool,
+
+    /// Skip this many documents before extracting, e.g. to preview part of a large publication
+    #[arg(long, default_value_t = 0)]
+    offset: usize,
+
+    /// Extract at most this many documents
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Whether the database content is encrypted; `auto` also reads plaintext databases
+    #[arg(long, value_enum, default_value_t = EncryptionMode::Auto)]
+    encryption: EncryptionMode,
+
+    /// How the manifest is serialized
+    #[arg(long, value_enum, default_value_t = OutputFormat::Pretty)]
+    format: OutputFormat,
+
+    /// Skip copying image files; documents still list their assets in the manifest
+    #[arg(long)]
+    no_assets: bool,
+
+    /// Name the image files after their SHA-256 (`ab/cd/<hash>.jpg`) to deduplicate them
+    #[arg(long, value_enum, default_value_t = AssetNaming::Original)]
+    asset_naming: AssetNaming,
+
+    /// Print the manifest to stdout instead of writing it to the output directory
+    #[arg(long)]
+    stdout: bool,
+
+    /// What to write: the manifest plus assets, or one self-contained HTML file per document
+    #[arg(long, value_enum, default_value_t = ExportFormat::Manifest, conflicts_with = "stdout")]
+    export: ExportFormat,
 
-    /// Output directory
-    #[arg(short, long)]
-    output: PathBuf,
+    /// Also write `references.json` to the output directory: every reference keyed by document id
+    #[arg(long, requires = "output")]
+    emit_references: bool,
+
+    /// Print the raw Publication row and the first N Document rows as JSON to stderr before
+    /// parsing, without decrypting anything (N defaults to 10)
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "10")]
+    debug_db: Option<usize>,
+}
+
+/// A publication on the CDN
+#[cfg(feature = "discovery")]
+#[derive(Args, Debug)]
+struct PublicationArgs {
+    /// Publication symbol, e.g. `w` or `lff`
+    #[arg(long = "pub")]
+    pub_name: String,
+
+    /// Language symbol
+    #[arg(long, default_value = "E")]
+    lang: String,
+
+    /// Issue tag (e.g. `20230100`); omit for publications without issues
+    #[arg(long)]
+    issue: Option<String>,
+}
+
+#[cfg(feature = "discovery")]
+#[derive(Args, Debug)]
+struct DownloadArgs {
+    #[command(flatten)]
+    publication: PublicationArgs,
+
+    /// Destination file, or a directory to save the file under its CDN name.
+    /// Defaults to the CDN name in the current directory.
+    #[arg(long)]
+    out: Option<PathBuf>,
+}
+
+#[cfg(feature = "discovery")]
+#[derive(Args, Debug)]
+struct FetchArgs {
+    #[command(flatten)]
+    publication: PublicationArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum OutputFormat {
+    /// Indented `manifest.json`
+    Pretty,
+    /// Single-line `manifest.json`
+    Compact,
+    /// `documents.ndjson`: one document per line, without the manifest envelope
+    Ndjson,
 }
 
 fn main() -> anyhow::Result<()> {
     env_logger::init();
-    let args = Args::parse();
+    let cli = Cli::parse();
+
+    // Status lines go to stderr so stdout only ever carries the manifest
+    eprintln!("🚀 Starting JW Parser (Rust Edition)");
+    match cli.command.unwrap_or(Command::Parse(cli.parse)) {
+        Command::Parse(args) => {
+            let input = args.input.as_ref().or(args.input_dir.as_ref()).expect("clap requires an input");
+            if !input.exists() {
+                eprintln!("❌ Input file does not exist!");
+                std::process::exit(1);
+            }
+            match &args.input_dir {
+                Some(input_dir) => run_batch(input_dir, &args.output),
+                None => run_parse(input, &args.output),
+            }
+        }
+        #[cfg(feature = "discovery")]
+        Command::Download(args) => {
+            let path = download(&args.publication, args.out.as_deref().unwrap_or(Path::new(".")))?;
+            eprintln!("✅ Downloaded to {:?}", path);
+            Ok(())
+        }
+        #[cfg(feature = "discovery")]
+        Command::Fetch(args) => {
+            // The downloaded file is only needed while parsing
+            let temp_dir = tempfile::tempdir()?;
+            let path = download(&args.publication, temp_dir.path())?;
+            run_parse(&path, &args.output)
+        }
+    }
+}
 
-    println!("🚀 Starting JW Parser (Rust Edition)");
-    println!("📂 Input: {:?}", args.input);
-    println!("📂 Output: {:?}", args.output);
+/// Resolves a publication on the CDN and downloads it to `out`, verifying the size and
+/// checksum. When `out` is a directory the file keeps its CDN name.
+#[cfg(feature = "discovery")]
+fn download(publication: &PublicationArgs, out: &Path) -> anyhow::Result<PathBuf> {
+    let config = DownloadConfig::default();
+    let file_info = DiscoveryService::find_file_with_config(
+        &publication.pub_name,
+        &publication.lang,
+        publication.issue.as_deref().unwrap_or_default(),
+        FileFormat::Jwpub,
+        &config,
+    )?;
+    eprintln!("🌐 Source: {}", file_info.url);
 
-    if !args.input.exists() {
-        eprintln!("❌ Input file does not exist!");
-        std::process::exit(1);
+    let path = if out.is_dir() {
+        let file_name = file_info
+            .url
+            .split(['?', '#'])
+            .next()
+            .and_then(|url| url.rsplit('/').next())
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Cannot derive a file name from {}", file_info.url))?;
+        out.join(file_name)
+    } else {
+        out.to_path_buf()
+    };
+
+    DiscoveryService::download_verified_with_config(&file_info, &path, &config)?;
+    Ok(path)
+}
+
+/// Parses a single file and writes the result as `args` asks
+fn run_parse(input: &Path, args: &OutputArgs) -> anyhow::Result<()> {
+    eprintln!("📂 Input: {:?}", input);
+    if let Some(output) = &args.output {
+        eprintln!("📂 Output: {:?}", output);
+    }
+
+    // Printed before parsing so it is there even when the parse fails
+    if let Some(document_limit) = args.debug_db {
+        match dump_database(input, document_limit) {
+            Ok(dump) => eprintln!("🗄️  Database:\n{}", serde_json::to_string_pretty(&dump)?),
+            Err(e) => eprintln!("⚠️  Could not read the database: {}", e),
+        }
     }
 
     let start = std::time::Instant::now();
+    let options = parse_options(args);
+
+    // The template is filled from the parsed manifest, so the images are kept in memory until
+    // the directory they go to is known. Standalone HTML inlines them, so it keeps them too.
+    let templated = args.output.is_some() && args.output_template.is_some();
+    let mut memory_assets = MemoryAssetSink::default();
+    let result = match (&args.output, args.export) {
+        (Some(output), ExportFormat::Manifest) if !templated => parse_jwpub_ex(input, output, &options),
+        _ => parse_jwpub_with_sink(input, &options, &mut memory_assets),
+    };
+    eprintln!();
+
+    let templated_output = match (&args.output, &args.output_template, &result) {
+        (Some(output), Some(template), Ok(parsed)) => {
+            let output = output.join(render_output_template(template, &parsed.manifest));
+            eprintln!("📂 Templated output: {:?}", output);
+            if args.export == ExportFormat::Manifest && options.extract_assets {
+                let mut sink = FsAssetSink::new(output.join("assets"))?;
+                for (name, bytes) in &memory_assets.assets {
+                    sink.write_asset(name, &mut bytes.as_slice())?;
+                }
+            }
+            Some(output)
+        }
+        _ => None,
+    };
+    let output_dir = templated_output.as_ref().or(args.output.as_ref());
+
+    match result {
+        Ok(output) => {
+            let stats = output.stats;
+            let manifest = output.manifest;
+            let destination = match output_dir {
+                Some(output) if args.export == ExportFormat::StandaloneHtml => {
+                    let paths = write_standalone_html(&manifest.documents, &memory_assets.assets, output)?;
+                    format!("{} HTML files in {:?}", paths.len(), output)
+                }
+                Some(output) if !args.stdout => {
+                    format!("{:?}", write_manifest_file(&manifest, args.format, output)?)
+                }
+                _ => {
+                    let mut writer = BufWriter::new(std::io::stdout().lock());
+                    write_manifest(&manifest, args.format, &mut writer)?;
+                    writer.flush()?;
+                    "stdout".to_string()
+                }
+            };
+            if let (Some(output), true) = (output_dir, args.emit_references) {
+                eprintln!("🔗 References saved to: {:?}", write_references_json(&manifest, output)?);
+            }
 
-    match parse_jwpub(&args.input, &args.output) {
-        Ok(manifest) => {
-            let json_path = args.output.join("manifest.json");
-            let json = serde_json::to_string_pretty(&manifest)?;
-            fs::write(&json_path, json)?;
-            
             let duration = start.elapsed();
-            println!("✅ Success! Parsed in {:.2?}", duration);
-            println!("📄 Manifest saved to: {:?}", json_path);
-            println!("📚 Documents processed: {}", manifest.documents.len());
+            eprintln!("✅ Success! Parsed in {:.2?}", duration);
+            eprintln!("📄 Output saved to: {}", destination);
+            eprintln!("📚 Documents processed: {} ({} bytes of HTML)", stats.documents, stats.decrypted_bytes);
+            eprintln!(
+                "🔗 References: {} bible, {} publication, {} video, {} audio",
+                stats.bible_references, stats.publication_references, stats.video_references, stats.audio_references
+            );
+            eprintln!("🖼️  Assets: {} images", stats.image_assets);
+            eprintln!(
+                "⏱️  Stages: open {:.2?}, documents {:.2?}, assets {:.2?}",
+                stats.timings.unzipping, stats.timings.documents, stats.timings.extracting_assets
+            );
+            if stats.failed_documents > 0 {
+                eprintln!("⚠️  Documents skipped: {}", stats.failed_documents);
+            }
+            if !manifest.missing_assets.is_empty() {
+                eprintln!("⚠️  Images missing from the archive: {}", manifest.missing_assets.join(", "));
+            }
         },
         Err(e) => {
             eprintln!("❌ Error parsing file: {}", e);
@@ -49,3 +323,148 @@ fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Builds the parse options from the command line
+fn parse_options(args: &OutputArgs) -> ParseOptions {
+    let mut builder = ParseOptions::builder()
+        .all_classes(args.all_classes)
+        .skip_failed(args.skip_failed)
+        .encryption(args.encryption)
+        .asset_naming(args.asset_naming)
+        .offset(args.offset)
+        // Without an output directory there is nowhere to put the images
+        .skip_assets(args.no_assets || args.output.is_none())
+        .on_progress(print_progress);
+    if let Some(class_id) = args.class_id {
+        builder = builder.class_id(class_id);
+    }
+    if let Some(limit) = args.limit {
+        builder = builder.limit(limit);
+    }
+    builder.build()
+}
+
+/// Parses a whole directory, reporting each file and a final summary
+fn run_batch(input_dir: &Path, args: &OutputArgs) -> anyhow::Result<()> {
+    let output = args.output.as_ref().expect("clap requires an output without --stdout");
+    eprintln!("📂 Input: {:?}", input_dir);
+    eprintln!("📂 Output: {:?}", output);
+
+    let start = std::time::Instant::now();
+    let format = args.format;
+    let items = parse_jwpub_dir(input_dir, output, &parse_options(args))?;
+    eprintln!();
+
+    let mut failures = Vec::new();
+    for item in &items {
+        match &item.result {
+            Ok((output_dir, output)) => {
+                write_manifest_file(&output.manifest, format, output_dir)?;
+                if args.emit_references {
+                    write_references_json(&output.manifest, output_dir)?;
+                }
+                eprintln!("✅ {:?} -> {:?} ({} documents)", item.input, output_dir, output.manifest.documents.len());
+            }
+            Err(e) => {
+                eprintln!("❌ {:?}: {}", item.input, e);
+                failures.push(&item.input);
+            }
+        }
+    }
+
+    eprintln!(
+        "📚 Processed {} files in {:.2?}: {} succeeded, {} failed",
+        items.len(),
+        start.elapsed(),
+        items.len() - failures.len(),
+        failures.len()
+    );
+    if !failures.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Fills the placeholders of an `--output-template`. Values are sanitized so they cannot add
+/// path separators or climb out of the output directory.
+fn render_output_template(template: &str, manifest: &Manifest) -> PathBuf {
+    let issue = match manifest.issue.as_str() {
+        "0" => "",
+        issue => issue,
+    };
+
+    let rendered = template
+        .replace("{symbol}", &sanitize_path_component(&manifest.publication))
+        .replace("{year}", &manifest.year.to_string())
+        .replace("{issue}", &sanitize_path_component(issue))
+        .replace("{language}", &sanitize_path_component(&manifest.language))
+        .replace("{title}", &sanitize_path_component(&manifest.title));
+
+    // Only plain components survive, so neither the template nor the values can escape
+    rendered
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty() && *component != "." && *component != "..")
+        .collect()
+}
+
+/// Writes the manifest into `output` under the file name matching `format`
+fn write_manifest_file(manifest: &Manifest, format: OutputFormat, output: &Path) -> anyhow::Result<PathBuf> {
+    fs::create_dir_all(output)?;
+    let json_path = output.join(match format {
+        OutputFormat::Ndjson => "documents.ndjson",
+        OutputFormat::Pretty | OutputFormat::Compact => "manifest.json",
+    });
+    let mut writer = BufWriter::new(fs::File::create(&json_path)?);
+    write_manifest(manifest, format, &mut writer)?;
+    writer.flush()?;
+    Ok(json_path)
+}
+
+/// Serializes the manifest in the requested format
+fn write_manifest(manifest: &Manifest, format: OutputFormat, writer: &mut dyn Write) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Pretty => {
+            serde_json::to_writer_pretty(&mut *writer, manifest)?;
+            writeln!(writer)?;
+        }
+        OutputFormat::Compact => {
+            serde_json::to_writer(&mut *writer, manifest)?;
+            writeln!(writer)?;
+        }
+        OutputFormat::Ndjson => {
+            for document in &manifest.documents {
+                serde_json::to_writer(&mut *writer, document)?;
+                writeln!(writer)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Renders a single-line progress bar on stderr
+fn print_progress(progress: ParseProgress) {
+    const WIDTH: usize = 30;
+
+    let label = match progress.stage {
+        ParseStage::Unzipping => "Unzipping",
+        ParseStage::Decrypting => "Decrypting",
+        ParseStage::ParsingHtml => "Parsing HTML",
+        ParseStage::ExtractingAssets => "Extracting assets",
+    };
+
+    if progress.total == 0 {
+        eprint!("\r⏳ {:<18}", label);
+    } else {
+        let done = (progress.current + 1).min(progress.total);
+        let filled = done * WIDTH / progress.total;
+        eprint!(
+            "\r⏳ {:<18} [{}{}] {}/{}",
+            label,
+            "#".repeat(filled),
+            "-".repeat(WIDTH - filled),
+            done,
+            progress.total
+        );
+    }
+    let _ = std::io::stderr().flush();
+}
@@ -1,36 +1,103 @@
-use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
 use anyhow::{anyhow, Result};
 use base64::{engine::general_purpose, Engine as _};
-use flate2::read::ZlibDecoder;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
 use sha2::{Digest, Sha256};
-use std::io::Read;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::Mutex;
+use crate::assets::sniff_image_extension;
+use crate::error::KeyError;
 
 type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
 
 // Master key from reference implementation
 const MASTER_KEY_BASE64: &str = "MTFjYmI1NTg3ZTMyODQ2ZDRjMjY3OTBjNjMzZGEyODlmNjZmZTU4NDJhM2E1ODVjZTFiYzNhMjk0YWY1YWRhNw==";
+// The master key is XORed against a SHA-256 digest, so it must cover all 32 bytes
+const MASTER_KEY_LEN: usize = 32;
 
+/// Leading bytes of a zstd frame
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Key and IV derived from a pub card
+type DerivedKeys = (Vec<u8>, Vec<u8>);
+
+/// How the content stored in the database is protected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "native", derive(clap::ValueEnum))]
+pub enum EncryptionMode {
+    /// Reads content that is already plain (compressed or not) as is and decrypts the rest
+    #[default]
+    Auto,
+    /// Always AES-128-CBC, as in the files published on JW.org
+    Aes128Cbc,
+    /// Never decrypts: databases extracted or modified by other tools
+    None,
+}
+
+/// Derives JWPUB keys and decrypts document blobs. It works on its own, without going through
+/// `parse_jwpub`: build one with `new` and either call `derive_and_decrypt` with a pub card, or
+/// `decrypt_and_inflate` directly with a known key/IV pair (e.g. for test fixtures).
 pub struct CryptoService {
     master_key: Vec<u8>,
+    /// Keys already derived, by pub card. A `Mutex` keeps the service `Sync` so it can be
+    /// shared by the parallel document pipeline or a long-lived server.
+    key_cache: Mutex<HashMap<String, DerivedKeys>>,
 }
 
 impl CryptoService {
-    pub fn new() -> Result<Self> {
+    /// Creates a service using the master key embedded in the crate
+    pub fn new() -> Result<Self, KeyError> {
+        Self::with_master_key_base64(MASTER_KEY_BASE64)
+    }
+
+    /// Creates a service from a base64 string wrapping the hex-encoded master key
+    /// (same encoding as the embedded constant)
+    pub fn with_master_key_base64(s: &str) -> Result<Self, KeyError> {
         let master_key_hex_bytes = general_purpose::STANDARD
-            .decode(MASTER_KEY_BASE64)
-            .map_err(|e| anyhow!("Failed to decode master key base64: {}", e))?;
+            .decode(s)
+            .map_err(|e| KeyError::InvalidBase64(e.to_string()))?;
         
         let master_key_hex_str = String::from_utf8(master_key_hex_bytes)
-            .map_err(|e| anyhow!("Invalid UTF-8 in master key: {}", e))?;
+            .map_err(|_| KeyError::NotUtf8)?;
 
-        let master_key = hex::decode(master_key_hex_str)
-            .map_err(|e| anyhow!("Failed to decode hex master key: {}", e))?;
+        let master_key = hex::decode(master_key_hex_str.trim())
+            .map_err(|e| KeyError::InvalidHex(e.to_string()))?;
         
-        Ok(Self { master_key })
+        Self::with_master_key(&master_key)
     }
 
-    /// Derives Key and IV based on the PubCard string (mepsLang_symbol_year_issue)
+    /// Creates a service from raw master key bytes, bypassing the embedded constant
+    pub fn with_master_key(key: &[u8]) -> Result<Self, KeyError> {
+        if key.len() != MASTER_KEY_LEN {
+            return Err(KeyError::WrongLength { got: key.len(), expected: MASTER_KEY_LEN });
+        }
+
+        Ok(Self {
+            master_key: key.to_vec(),
+            key_cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Derives Key and IV based on the PubCard string (mepsLang_symbol_year_issue).
+    /// Results are memoized per pub card until `clear_key_cache` is called.
     pub fn derive_keys(&self, pub_card: &str) -> (Vec<u8>, Vec<u8>) {
+        let mut cache = self.key_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        cache
+            .entry(pub_card.to_string())
+            .or_insert_with(|| self.compute_keys(pub_card))
+            .clone()
+    }
+
+    /// Forgets every memoized key
+    pub fn clear_key_cache(&self) {
+        self.key_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+    }
+
+    fn compute_keys(&self, pub_card: &str) -> DerivedKeys {
         let mut hasher = Sha256::new();
         hasher.update(pub_card.as_bytes());
         let hash = hasher.finalize();
@@ -47,22 +114,271 @@ impl CryptoService {
         (key, iv)
     }
 
-    /// Decrypts AES-128-CBC encrypted content and then decompresses it using Zlib
+    /// Decrypts AES-128-CBC encrypted content and then decompresses it.
+    ///
+    /// The payload is usually zlib, but gzip-wrapped and uncompressed documents also occur,
+    /// so the format is picked from the leading magic bytes (see `inflate`).
     pub fn decrypt_and_inflate(&self, encrypted_data: &[u8], key: &[u8], iv: &[u8]) -> Result<String> {
         // 1. Decrypt
+        let decrypted_bytes = self.decrypt(encrypted_data, key, iv)?;
+
+        // 2. Inflate (Decompress)
+        Self::inflate(&decrypted_bytes)
+    }
+
+    /// Inverse of `decrypt_and_inflate`: zlib-compresses the HTML and encrypts it with
+    /// AES-128-CBC, as the `Document.Content` of a JWPUB stores it
+    pub fn encrypt_and_deflate(&self, html: &str, key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(html.as_bytes())?;
+        let compressed = encoder.finish()?;
+
+        self.encrypt(&compressed, key, iv)
+    }
+
+    /// Derives the keys for `pub_card` and decrypts/inflates `encrypted_data` with them
+    pub fn derive_and_decrypt(&self, pub_card: &str, encrypted_data: &[u8]) -> Result<String> {
+        let (key, iv) = self.derive_keys(pub_card);
+        self.decrypt_and_inflate(encrypted_data, &key, &iv)
+    }
+
+    /// Reads a document blob as `mode` dictates. `Auto` first tries to inflate the blob as
+    /// stored and only decrypts it when that does not give readable text.
+    pub fn read_content(&self, data: &[u8], key: &[u8], iv: &[u8], mode: EncryptionMode) -> Result<String> {
+        match mode {
+            EncryptionMode::Aes128Cbc => self.decrypt_and_inflate(data, key, iv),
+            EncryptionMode::None => Self::inflate(data),
+            EncryptionMode::Auto => match Self::inflate(data) {
+                Ok(text) if Self::looks_like_document(&text) => Ok(text),
+                _ => self.decrypt_and_inflate(data, key, iv),
+            },
+        }
+    }
+
+    /// Reads an image blob as `mode` dictates. `Auto` keeps blobs that already start with a
+    /// known image signature.
+    pub fn read_blob(&self, data: &[u8], key: &[u8], iv: &[u8], mode: EncryptionMode) -> Result<Vec<u8>> {
+        match mode {
+            EncryptionMode::Aes128Cbc => self.decrypt(data, key, iv),
+            EncryptionMode::None => Ok(data.to_vec()),
+            EncryptionMode::Auto if sniff_image_extension(data).is_some() => Ok(data.to_vec()),
+            EncryptionMode::Auto => self.decrypt(data, key, iv),
+        }
+    }
+
+    /// Checks that `key`/`iv` really open `sample_content`: a wrong key can still pass PKCS7
+    /// unpadding, so the result must also inflate and look like HTML (or at least readable text)
+    pub fn verify_keys(&self, sample_content: &[u8], key: &[u8], iv: &[u8]) -> bool {
+        self.verify_content(sample_content, key, iv, EncryptionMode::Aes128Cbc)
+    }
+
+    /// Same as `verify_keys`, reading `sample_content` as `mode` dictates
+    pub fn verify_content(&self, sample_content: &[u8], key: &[u8], iv: &[u8], mode: EncryptionMode) -> bool {
+        match self.read_content(sample_content, key, iv, mode) {
+            Ok(text) => Self::looks_like_document(&text),
+            // Without the `zstd` feature a zstd payload cannot be read, but the keys did open it;
+            // the documents then fail with an error naming the missing feature
+            Err(_) if !cfg!(feature = "zstd") && mode != EncryptionMode::None => self
+                .decrypt(sample_content, key, iv)
+                .is_ok_and(|bytes| bytes.starts_with(&ZSTD_MAGIC)),
+            Err(_) => false,
+        }
+    }
+
+    /// HTML, or at least text without control characters
+    fn looks_like_document(text: &str) -> bool {
+        let text = text.trim_start_matches('\u{feff}').trim_start();
+        text.starts_with('<')
+            || (!text.is_empty() && text.chars().take(64).all(|c| !c.is_control() || c.is_whitespace()))
+    }
+
+    /// Decrypts AES-128-CBC encrypted content without decompressing it (e.g. image blobs)
+    pub fn decrypt(&self, encrypted_data: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+        if key.len() != 16 || iv.len() != 16 {
+            return Err(anyhow!("AES-128-CBC needs a 16-byte key and IV, got {} and {}", key.len(), iv.len()));
+        }
         let decryptor = Aes128CbcDec::new(key.into(), iv.into());
         // We clone data because decrypt_padded_mut modifies the buffer in place
         let mut buffer = encrypted_data.to_vec();
-        
-        let decrypted_bytes = decryptor
+
+        let len = decryptor
             .decrypt_padded_mut::<Pkcs7>(&mut buffer)
-            .map_err(|e| anyhow!("AES decryption failed: {}", e))?;
+            .map_err(|e| anyhow!("AES decryption failed: {}", e))?
+            .len();
+        buffer.truncate(len);
 
-        // 2. Inflate (Decompress)
-        let mut decoder = ZlibDecoder::new(decrypted_bytes);
+        Ok(buffer)
+    }
+
+    /// Encrypts with AES-128-CBC and PKCS7 padding, the inverse of `decrypt`
+    pub fn encrypt(&self, data: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+        if key.len() != 16 || iv.len() != 16 {
+            return Err(anyhow!("AES-128-CBC needs a 16-byte key and IV, got {} and {}", key.len(), iv.len()));
+        }
+        let encryptor = Aes128CbcEnc::new(key.into(), iv.into());
+        // Padding adds between 1 and 16 bytes
+        let mut buffer = vec![0u8; data.len() + 16];
+        buffer[..data.len()].copy_from_slice(data);
+
+        let len = encryptor
+            .encrypt_padded_mut::<Pkcs7>(&mut buffer, data.len())
+            .map_err(|e| anyhow!("AES encryption failed: {}", e))?
+            .len();
+        buffer.truncate(len);
+
+        Ok(buffer)
+    }
+
+    /// Decompresses a decrypted payload: `0x78` starts a zlib stream, `0x1f 0x8b` a gzip one,
+    /// `0x28 0xb5 0x2f 0xfd` a zstd frame (with the `zstd` feature), and anything else is taken
+    /// as plain UTF-8
+    fn inflate(data: &[u8]) -> Result<String> {
         let mut s = String::new();
-        decoder.read_to_string(&mut s)?;
+        match data {
+            [0x78, ..] => {
+                ZlibDecoder::new(data)
+                    .read_to_string(&mut s)
+                    .map_err(|e| anyhow!("Zlib inflate failed: {}", e))?;
+            }
+            [0x1f, 0x8b, ..] => {
+                GzDecoder::new(data)
+                    .read_to_string(&mut s)
+                    .map_err(|e| anyhow!("Gzip inflate failed: {}", e))?;
+            }
+            #[cfg(feature = "zstd")]
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => {
+                zstd::stream::read::Decoder::new(data)
+                    .and_then(|mut decoder| decoder.read_to_string(&mut s))
+                    .map_err(|e| anyhow!("Zstd decompression failed: {}", e))?;
+            }
+            #[cfg(not(feature = "zstd"))]
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => {
+                return Err(anyhow!("Document is zstd-compressed; build jw_parser with the `zstd` feature"));
+            }
+            _ => {
+                s = String::from_utf8(data.to_vec())
+                    .map_err(|e| anyhow!("Uncompressed document is not valid UTF-8: {}", e))?;
+            }
+        }
 
         Ok(s)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+
+    const HTML: &str = "<p id=\"p1\">Hello</p>";
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn inflates_zlib() {
+        let data = zlib(HTML.as_bytes());
+        assert_eq!(data[0], 0x78);
+        assert_eq!(CryptoService::inflate(&data).unwrap(), HTML);
+    }
+
+    #[test]
+    fn inflates_gzip() {
+        let data = gzip(HTML.as_bytes());
+        assert_eq!(data[..2], [0x1f, 0x8b]);
+        assert_eq!(CryptoService::inflate(&data).unwrap(), HTML);
+    }
+
+    #[test]
+    fn passes_plain_text_through() {
+        assert_eq!(CryptoService::inflate(HTML.as_bytes()).unwrap(), HTML);
+        assert!(CryptoService::inflate(&[b'<', 0xff, 0xfe]).is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn inflates_zstd() {
+        let data = zstd::encode_all(HTML.as_bytes(), 0).unwrap();
+        assert_eq!(CryptoService::inflate(&data).unwrap(), HTML);
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn zstd_needs_the_feature() {
+        let error = CryptoService::inflate(&[0x28, 0xb5, 0x2f, 0xfd, 0]).unwrap_err();
+        assert!(error.to_string().contains("zstd"));
+    }
+
+    #[test]
+    fn decrypts_each_compression_format() {
+        let crypto = CryptoService::new().unwrap();
+        let (key, iv) = crypto.derive_keys("0_w_2023_20230100");
+        for payload in [zlib(HTML.as_bytes()), gzip(HTML.as_bytes()), HTML.as_bytes().to_vec()] {
+            let encrypted = crypto.encrypt(&payload, &key, &iv).unwrap();
+            assert_eq!(crypto.decrypt_and_inflate(&encrypted, &key, &iv).unwrap(), HTML);
+        }
+    }
+
+    #[test]
+    fn decrypts_standalone_with_a_known_key() {
+        let crypto = CryptoService::new().unwrap();
+        let key = [7u8; 16];
+        let iv = [9u8; 16];
+        let encrypted = crypto.encrypt_and_deflate(HTML, &key, &iv).unwrap();
+
+        assert_eq!(crypto.decrypt_and_inflate(&encrypted, &key, &iv).unwrap(), HTML);
+        assert!(crypto.verify_keys(&encrypted, &key, &iv));
+        assert!(!crypto.verify_keys(&encrypted, &[8u8; 16], &iv));
+    }
+
+    #[test]
+    fn derive_and_decrypt_uses_the_pub_card_keys() {
+        let crypto = CryptoService::new().unwrap();
+        let (key, iv) = crypto.derive_keys("0_w_2023_20230100");
+        let encrypted = crypto.encrypt_and_deflate(HTML, &key, &iv).unwrap();
+
+        assert_eq!(crypto.derive_and_decrypt("0_w_2023_20230100", &encrypted).unwrap(), HTML);
+        assert!(crypto.derive_and_decrypt("0_w_2023_20230200", &encrypted).is_err());
+    }
+
+    #[test]
+    fn rejects_keys_of_the_wrong_length() {
+        let crypto = CryptoService::new().unwrap();
+        let encrypted = crypto.encrypt_and_deflate(HTML, &[7u8; 16], &[9u8; 16]).unwrap();
+
+        assert!(crypto.decrypt_and_inflate(&encrypted, &[7u8; 8], &[9u8; 16]).is_err());
+        assert!(crypto.decrypt(&encrypted, &[7u8; 16], &[]).is_err());
+    }
+
+    #[test]
+    fn encrypt_is_the_inverse_of_decrypt() {
+        let crypto = CryptoService::new().unwrap();
+        let (key, iv) = crypto.derive_keys("0_w_2023_20230100");
+        // Lengths around the 16-byte block size exercise the padding
+        for len in [0, 1, 15, 16, 17, 1000] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let encrypted = crypto.encrypt(&data, &key, &iv).unwrap();
+            assert_eq!(encrypted.len() % 16, 0);
+            assert_eq!(crypto.decrypt(&encrypted, &key, &iv).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn encrypt_and_deflate_round_trips() {
+        let crypto = CryptoService::new().unwrap();
+        let (key, iv) = crypto.derive_keys("0_w_2023_20230100");
+        let html = "<h1>Título</h1><p id=\"p1\">Texto con acentos y “comillas”</p>".repeat(50);
+
+        let encrypted = crypto.encrypt_and_deflate(&html, &key, &iv).unwrap();
+        assert_eq!(crypto.decrypt_and_inflate(&encrypted, &key, &iv).unwrap(), html);
+    }
+}
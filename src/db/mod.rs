@@ -1,6 +1,14 @@
-use anyhow::Result;
-use rusqlite::{Connection, OpenFlags};
-use crate::models::DbPublication;
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use rusqlite::serialize::OwnedData;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{Connection, DatabaseName, OpenFlags, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use std::ptr::NonNull;
+use crate::crypto::{CryptoService, EncryptionMode};
+use crate::html::RewritePolicy;
+use crate::models::{DatabaseDump, DbPublication, Document, DocumentRowDump, DocumentRow, DocumentSummary, MultimediaRow, TocEntry, VerseRow};
+use crate::JwpubError;
 
 pub struct DatabaseService {
     conn: Connection,
@@ -13,40 +21,158 @@ impl DatabaseService {
         Ok(Self { conn })
     }
 
-    /// Retrieves publication metadata required for key derivation
-    pub fn get_publication_data(&self) -> Result<DbPublication> {
-        let mut stmt = self.conn.prepare(
-            "SELECT MepsLanguageIndex, Symbol, Year, IssueTagNumber FROM Publication LIMIT 1"
+    /// Loads an SQLite database image straight into memory (read-only), without a temp file
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Self::deserialize(data, true)
+    }
+
+    /// Like `from_bytes`, but the in-memory copy can be modified and saved with `to_bytes`
+    pub fn from_bytes_writable(data: &[u8]) -> Result<Self> {
+        Self::deserialize(data, false)
+    }
+
+    fn deserialize(data: &[u8], read_only: bool) -> Result<Self> {
+        let mut image = data.to_vec();
+        // Databases left in WAL mode cannot be deserialized; flag them as rollback-journal instead
+        if image.len() > 19 && image[18] == 2 && image[19] == 2 {
+            image[18] = 1;
+            image[19] = 1;
+        }
+
+        // SAFETY: the buffer comes from sqlite3_malloc64 as OwnedData requires, and is fully
+        // initialized with `image.len()` bytes before ownership is handed to SQLite.
+        let owned = unsafe {
+            let ptr = rusqlite::ffi::sqlite3_malloc64(image.len() as u64) as *mut u8;
+            let ptr = NonNull::new(ptr)
+                .ok_or_else(|| anyhow!("SQLite could not allocate {} bytes for the database", image.len()))?;
+            std::ptr::copy_nonoverlapping(image.as_ptr(), ptr.as_ptr(), image.len());
+            OwnedData::from_raw_nonnull(ptr, image.len())
+        };
+
+        let mut conn = Connection::open_in_memory()?;
+        conn.deserialize(DatabaseName::Main, owned, read_only)?;
+        Ok(Self { conn })
+    }
+
+    /// Serializes the database back into an SQLite file image
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(self.conn.serialize(DatabaseName::Main)?.to_vec())
+    }
+
+    /// Replaces the stored `Content` of a document; `false` when no document has that id
+    pub fn set_document_content(&self, doc_id: u32, content: &[u8]) -> Result<bool> {
+        let updated = self.conn.execute(
+            "UPDATE Document SET Content = ? WHERE MepsDocumentId = ?",
+            rusqlite::params![content, doc_id],
         )?;
+        Ok(updated > 0)
+    }
+
+    /// Retrieves publication metadata required for key derivation, plus its display titles.
+    ///
+    /// Column names are probed first so schema variants (e.g. `Issue` instead of
+    /// `IssueTagNumber`, stored as text or integer) are read the same way.
+    pub fn get_publication_data(&self) -> Result<DbPublication> {
+        let columns = self.table_columns("Publication")?;
+        if columns.is_empty() {
+            return Err(anyhow!("Publication table not found in DB"));
+        }
+
+        let sql = format!(
+            "SELECT {}, {}, {}, {}, {}, {}, {}, {} FROM Publication p LIMIT 1",
+            Self::required_column(&columns, "Publication", "p", &["MepsLanguageIndex"])?,
+            Self::required_column(&columns, "Publication", "p", &["Symbol"])?,
+            Self::required_column(&columns, "Publication", "p", &["Year"])?,
+            Self::required_column(&columns, "Publication", "p", &["IssueTagNumber", "Issue"])?,
+            Self::column_or_null(&columns, "p", &["Title"]),
+            Self::column_or_null(&columns, "p", &["ShortTitle", "UndatedTitle"]),
+            Self::column_or_null(&columns, "p", &["PublicationType"]),
+            Self::column_or_null(&columns, "p", &["PublicationCategorySymbol"]),
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
 
         let mut rows = stmt.query([])?;
 
         if let Some(row) = rows.next()? {
+            let issue_tag_number = match row.get_ref(3)? {
+                ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+                ValueRef::Integer(number) => number.to_string(),
+                ValueRef::Real(number) => (number as i64).to_string(),
+                ValueRef::Null => "0".to_string(),
+                ValueRef::Blob(_) => return Err(anyhow!("Publication issue column holds a BLOB")),
+            };
+
             Ok(DbPublication {
                 meps_language_index: row.get(0)?,
                 symbol: row.get(1)?,
                 year: row.get(2)?,
-                issue_tag_number: row.get::<_, String>(3)?,
+                issue_tag_number,
+                title: row.get(4)?,
+                short_title: row.get(5)?,
+                publication_type: Self::text_or_none(row.get_ref(6)?),
+                category_symbol: Self::text_or_none(row.get_ref(7)?),
             })
         } else {
             Err(anyhow::anyhow!("No publication data found in DB"))
         }
     }
 
-    /// Retrieves raw encrypted content for documents of a specific class
-    /// Returns a tuple of (MepsDocumentId, Title, EncryptedContent)
-    pub fn get_documents_by_class(&self, class_id: i32) -> Result<Vec<(u32, String, Vec<u8>)>> {
+    /// Looks up the language symbol for a MEPS index in the optional `Language` table
+    pub fn get_language_symbol(&self, meps_index: i32) -> Result<Option<String>> {
+        self.language_column(meps_index, &["Symbol"])
+    }
+
+    /// Reads the right-to-left flag (`IsRTL`/`IsRightToLeft`) of a MEPS index from the optional
+    /// `Language` table; `None` when the table or the column is missing
+    pub fn get_language_is_rtl(&self, meps_index: i32) -> Result<Option<bool>> {
+        self.language_column(meps_index, &["IsRTL", "IsRightToLeft"])
+    }
+
+    /// Reads the first of `candidates` present in the `Language` row of a MEPS index
+    fn language_column<T: rusqlite::types::FromSql>(&self, meps_index: i32, candidates: &[&str]) -> Result<Option<T>> {
+        let columns = self.table_columns("Language")?;
+        let find = |names: &[&str]| names.iter().find(|name| columns.contains(&name.to_lowercase())).map(|name| name.to_string());
+        let value_column = find(candidates);
+        let index_column = find(&["MepsLanguageIndex", "MepsLanguageId", "LanguageId"]);
+        let (value_column, index_column) = match (value_column, index_column) {
+            (Some(value_column), Some(index_column)) => (value_column, index_column),
+            _ => return Ok(None),
+        };
+
+        let sql = format!("SELECT {} FROM Language WHERE {} = ? LIMIT 1", value_column, index_column);
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut rows = stmt.query([meps_index])?;
+
+        match rows.next()? {
+            Some(row) => Ok(row.get(0)?),
+            None => Ok(None),
+        }
+    }
+
+    /// Lists every distinct document class present in the publication
+    pub fn get_all_document_classes(&self) -> Result<Vec<i32>> {
         let mut stmt = self.conn.prepare(
-            "SELECT MepsDocumentId, Title, Content FROM Document WHERE Class = ?"
+            "SELECT DISTINCT Class FROM Document ORDER BY Class"
         )?;
 
-        let rows = stmt.query_map([class_id], |row| {
-            Ok((
-                row.get(0)?,
-                row.get(1)?,
-                row.get(2)?,
-            ))
-        })?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+
+        let mut classes = Vec::new();
+        for class in rows {
+            classes.push(class?);
+        }
+
+        Ok(classes)
+    }
+
+    /// Retrieves the rows (titles and raw encrypted content) of the documents of a class, in
+    /// `DocumentId` order. A NULL Content column is returned as an empty buffer; base64-stored
+    /// content is decoded.
+    pub fn get_documents_by_class(&self, class_id: i32) -> Result<Vec<DocumentRow>> {
+        let sql = format!("{} WHERE d.Class = ? ORDER BY d.DocumentId", self.document_row_select()?);
+        let mut stmt = self.conn.prepare(&sql)?;
+
+        let rows = stmt.query_map([class_id], Self::document_row)?;
 
         let mut documents = Vec::new();
         for doc in rows {
@@ -55,4 +181,665 @@ impl DatabaseService {
 
         Ok(documents)
     }
+
+    /// Id, title and class of every document, in database order
+    pub fn get_document_summaries(&self) -> Result<Vec<DocumentSummary>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT MepsDocumentId, Title, Class FROM Document ORDER BY DocumentId"
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(DocumentSummary {
+                id: row.get(0)?,
+                title: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                class: row.get(2)?,
+            })
+        })?;
+
+        let mut summaries = Vec::new();
+        for summary in rows {
+            summaries.push(summary?);
+        }
+
+        Ok(summaries)
+    }
+
+    /// The `Publication` row and the first `document_limit` `Document` rows exactly as stored,
+    /// to tell a database problem from a decryption one
+    pub fn dump(&self, document_limit: usize) -> Result<DatabaseDump> {
+        let mut stmt = self.conn.prepare("SELECT * FROM Publication LIMIT 1")?;
+        let names: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        let publication = stmt
+            .query_row([], |row| {
+                names
+                    .iter()
+                    .enumerate()
+                    .map(|(index, name)| Ok((name.clone(), Self::json_value(row.get_ref(index)?))))
+                    .collect::<rusqlite::Result<serde_json::Map<_, _>>>()
+            })
+            .optional()?
+            .unwrap_or_default();
+
+        let document_count: i64 = self.conn.query_row("SELECT COUNT(*) FROM Document", [], |row| row.get(0))?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT MepsDocumentId, Title, length(Content) FROM Document ORDER BY DocumentId LIMIT ?"
+        )?;
+        let limit = i64::try_from(document_limit).unwrap_or(i64::MAX);
+        let rows = stmt.query_map([limit], |row| {
+            Ok(DocumentRowDump {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                content_length: row.get(2)?,
+            })
+        })?;
+
+        let mut documents = Vec::new();
+        for document in rows {
+            documents.push(document?);
+        }
+
+        Ok(DatabaseDump { publication, document_count: document_count as usize, documents })
+    }
+
+    /// JSON for a column value; blobs become a short description instead of their bytes
+    fn json_value(value: ValueRef) -> serde_json::Value {
+        match value {
+            ValueRef::Null => serde_json::Value::Null,
+            ValueRef::Integer(number) => number.into(),
+            ValueRef::Real(number) => number.into(),
+            ValueRef::Text(text) => String::from_utf8_lossy(text).into(),
+            ValueRef::Blob(bytes) => format!("<blob, {} bytes>", bytes.len()).into(),
+        }
+    }
+
+    /// MepsDocumentIds of the documents of a class that have content, in `DocumentId` order
+    pub fn get_document_ids_by_class(&self, class_id: i32) -> Result<Vec<u32>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT MepsDocumentId FROM Document WHERE Class = ? AND Content IS NOT NULL AND length(Content) > 0 ORDER BY DocumentId"
+        )?;
+
+        let rows = stmt.query_map([class_id], |row| row.get(0))?;
+
+        let mut ids = Vec::new();
+        for id in rows {
+            ids.push(id?);
+        }
+
+        Ok(ids)
+    }
+
+    /// MepsDocumentId of the first document (any class) that has content
+    pub fn get_first_document_id(&self) -> Result<Option<u32>> {
+        let id = self.conn.query_row(
+            "SELECT MepsDocumentId FROM Document WHERE Content IS NOT NULL AND length(Content) > 0 ORDER BY DocumentId LIMIT 1",
+            [],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(id)
+    }
+
+    /// Number of rows in the `Publication` table; fails when the table does not exist
+    pub fn get_publication_count(&self) -> Result<usize> {
+        let count: i64 = self.conn.query_row("SELECT COUNT(*) FROM Publication", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// Decrypts and parses the documents of a class one at a time, so only the document being
+    /// consumed is held in memory (unlike `get_documents_by_class`, which loads every blob).
+    pub fn documents_iter<'a>(
+        &'a self,
+        class_id: i32,
+        crypto: &'a CryptoService,
+        key: &'a [u8],
+        iv: &'a [u8],
+    ) -> Result<DocumentsIter<'a>> {
+        Ok(DocumentsIter {
+            db: self,
+            crypto,
+            key,
+            iv,
+            class_id,
+            policy: RewritePolicy::default(),
+            encryption: EncryptionMode::default(),
+            ids: self.get_document_ids_by_class(class_id)?.into_iter(),
+        })
+    }
+
+    /// Retrieves the row (titles and raw encrypted content) of a single document by
+    /// MepsDocumentId. A NULL Content column is returned as an empty buffer; base64-stored
+    /// content is decoded.
+    pub fn get_document(&self, doc_id: u32) -> Result<Option<DocumentRow>> {
+        let sql = format!("{} WHERE d.MepsDocumentId = ? LIMIT 1", self.document_row_select()?);
+        let document = self.conn.query_row(&sql, [doc_id], Self::document_row).optional()?;
+        Ok(document)
+    }
+
+    /// `SELECT` of the columns read by `document_row`. The contextual titles are missing from
+    /// some schema versions and read as NULL there.
+    fn document_row_select(&self) -> Result<String> {
+        let columns = self.table_columns("Document")?;
+        Ok(format!(
+            "SELECT d.MepsDocumentId, d.Title, {}, {}, d.Content FROM Document d",
+            Self::column_or_null(&columns, "d", &["ContextTitle"]),
+            Self::column_or_null(&columns, "d", &["FeatureTitle"]),
+        ))
+    }
+
+    fn document_row(row: &rusqlite::Row) -> rusqlite::Result<DocumentRow> {
+        Ok(DocumentRow {
+            id: row.get(0)?,
+            title: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+            context_title: Self::text_or_none(row.get_ref(2)?),
+            feature_title: Self::text_or_none(row.get_ref(3)?),
+            content: Self::content_bytes(row.get_ref(4)?),
+        })
+    }
+
+    /// Class of a single document by MepsDocumentId
+    pub fn get_document_class(&self, doc_id: u32) -> Result<Option<i32>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT Class FROM Document WHERE MepsDocumentId = ? LIMIT 1"
+        )?;
+        let mut rows = stmt.query([doc_id])?;
+
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// First and last dates covered by the publication (e.g. the weeks of the study articles)
+    /// as `YYYY-MM-DD`. Read from the dated-text columns of `Publication`, or else from the
+    /// span of the `DatedText` table; `None` for publications without dated texts.
+    pub fn get_date_range(&self) -> Result<Option<(String, String)>> {
+        let publication_columns = self.table_columns("Publication")?;
+        let first = Self::column_or_null(&publication_columns, "p", &["FirstDatedTextDateOffset", "FirstDatedText"]);
+        let last = Self::column_or_null(&publication_columns, "p", &["LastDatedTextDateOffset", "LastDatedText"]);
+        let sql = if first != "NULL" && last != "NULL" {
+            format!("SELECT {}, {} FROM Publication p LIMIT 1", first, last)
+        } else {
+            let columns = self.table_columns("DatedText")?;
+            if !columns.contains("firstdateoffset") || !columns.contains("lastdateoffset") {
+                return Ok(None);
+            }
+            "SELECT MIN(FirstDateOffset), MAX(LastDateOffset) FROM DatedText".to_string()
+        };
+
+        let range = self.conn.query_row(&sql, [], |row| {
+            Ok(Self::date_text(row.get_ref(0)?).zip(Self::date_text(row.get_ref(1)?)))
+        }).optional()?;
+        Ok(range.flatten())
+    }
+
+    /// Version of the database schema, which differs between generations of the JWPUB format.
+    /// Read from `Publication.SchemaVersion`, or else from `PRAGMA user_version` when it is set;
+    /// `None` when neither is available.
+    pub fn schema_version(&self) -> Result<Option<String>> {
+        let columns = self.table_columns("Publication")?;
+        if columns.contains("schemaversion") {
+            let version = self.conn.query_row("SELECT SchemaVersion FROM Publication LIMIT 1", [], |row| {
+                Ok(match row.get_ref(0)? {
+                    ValueRef::Integer(number) => Some(number.to_string()),
+                    ValueRef::Text(text) => Some(String::from_utf8_lossy(text).trim().to_string()),
+                    _ => None,
+                })
+            }).optional()?.flatten();
+            if let Some(version) = version.filter(|version| !version.is_empty()) {
+                return Ok(Some(version));
+            }
+        }
+
+        let user_version: i64 = self.conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        Ok((user_version != 0).then(|| user_version.to_string()))
+    }
+
+    /// Formats a date stored as a `YYYYMMDD` number as `YYYY-MM-DD`; other text is kept as is.
+    /// NULL and zero mean no date.
+    fn date_text(value: ValueRef) -> Option<String> {
+        let text = match value {
+            ValueRef::Integer(0) | ValueRef::Null => return None,
+            ValueRef::Integer(number) => number.to_string(),
+            ValueRef::Text(text) => String::from_utf8_lossy(text).trim().to_string(),
+            _ => return None,
+        };
+
+        if text.len() == 8 && text.bytes().all(|b| b.is_ascii_digit()) {
+            return Some(format!("{}-{}-{}", &text[..4], &text[4..6], &text[6..]));
+        }
+        (!text.is_empty() && text != "0").then_some(text)
+    }
+
+    /// Builds the table of contents from the `PublicationViewItem` hierarchy. Only the first
+    /// publication view is used when the database holds several. Returns an empty list when
+    /// the table does not exist.
+    pub fn get_toc(&self) -> Result<Vec<TocEntry>> {
+        let columns = self.table_columns("PublicationViewItem")?;
+        if columns.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let view_filter = if columns.contains("publicationviewid") {
+            "WHERE i.PublicationViewId = (SELECT MIN(PublicationViewId) FROM PublicationViewItem)"
+        } else {
+            ""
+        };
+        let default_document = Self::column_or_null(&columns, "i", &["DefaultDocumentId"]);
+        let sql = format!(
+            "SELECT i.PublicationViewItemId, {}, {}, d.MepsDocumentId FROM PublicationViewItem i \
+             LEFT JOIN Document d ON d.DocumentId = {} {} ORDER BY i.PublicationViewItemId",
+            Self::column_or_null(&columns, "i", &["ParentPublicationViewItemId"]),
+            Self::column_or_null(&columns, "i", &["Title"]),
+            default_document,
+            view_filter,
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<i64>>(1)?,
+                row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                row.get::<_, Option<u32>>(3)?,
+            ))
+        })?;
+
+        let mut items = Vec::new();
+        for row in rows {
+            items.push(row?);
+        }
+
+        // Items whose parent is not in the view (usually -1) are roots
+        let ids: HashSet<i64> = items.iter().map(|(id, ..)| *id).collect();
+        let mut children: HashMap<Option<i64>, Vec<usize>> = HashMap::new();
+        for (index, (_, parent, ..)) in items.iter().enumerate() {
+            let parent = parent.filter(|parent| ids.contains(parent));
+            children.entry(parent).or_default().push(index);
+        }
+
+        Ok(Self::toc_children(&items, &children, None))
+    }
+
+    fn toc_children(
+        items: &[(i64, Option<i64>, String, Option<u32>)],
+        children: &HashMap<Option<i64>, Vec<usize>>,
+        parent: Option<i64>,
+    ) -> Vec<TocEntry> {
+        children
+            .get(&parent)
+            .map(|indexes| {
+                indexes
+                    .iter()
+                    .map(|&index| {
+                        let (id, _, title, doc_id) = &items[index];
+                        TocEntry {
+                            title: title.clone(),
+                            doc_id: *doc_id,
+                            children: Self::toc_children(items, children, Some(*id)),
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Retrieves the images/videos linked to a document (by MepsDocumentId) through
+    /// the `DocumentMultimedia` table. Columns missing from the schema come back as `None`.
+    pub fn get_multimedia_for_document(&self, doc_id: u32) -> Result<Vec<MultimediaRow>> {
+        let columns = self.table_columns("Multimedia")?;
+        if columns.is_empty() || self.table_columns("DocumentMultimedia")?.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let sql = format!(
+            "SELECT {}, {}, {}, {}, {}, {} FROM Multimedia m \
+             JOIN DocumentMultimedia dm ON dm.MultimediaId = m.MultimediaId \
+             JOIN Document d ON d.DocumentId = dm.DocumentId \
+             WHERE d.MepsDocumentId = ? ORDER BY m.MultimediaId",
+            Self::column_or_null(&columns, "m", &["FilePath"]),
+            Self::column_or_null(&columns, "m", &["Caption"]),
+            Self::column_or_null(&columns, "m", &["AltText", "Label"]),
+            Self::column_or_null(&columns, "m", &["Width"]),
+            Self::column_or_null(&columns, "m", &["Height"]),
+            Self::column_or_null(&columns, "m", &["MimeType"]),
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+
+        let rows = stmt.query_map([doc_id], |row| {
+            Ok(MultimediaRow {
+                file_path: row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+                caption: row.get(1)?,
+                alt_text: row.get(2)?,
+                width: row.get(3)?,
+                height: row.get(4)?,
+                mime_type: row.get(5)?,
+            })
+        })?;
+
+        let mut multimedia = Vec::new();
+        for row in rows {
+            multimedia.push(row?);
+        }
+
+        Ok(multimedia)
+    }
+
+    /// File name of the publication cover: the `Multimedia` row the `Publication` table points
+    /// to through `CoverId`/`CoverMultimediaId`, or else the first image whose name marks it as
+    /// a cover (`_cvr`). `None` when neither exists.
+    pub fn get_cover_file_name(&self) -> Result<Option<String>> {
+        let columns = self.table_columns("Multimedia")?;
+        let file_column = Self::column_or_null(&columns, "m", &["FilePath", "FileName"]);
+        if file_column == "NULL" {
+            return Ok(None);
+        }
+
+        let publication_columns = self.table_columns("Publication")?;
+        if let Some(cover_column) = ["CoverId", "CoverMultimediaId"]
+            .iter()
+            .find(|name| publication_columns.contains(&name.to_lowercase()))
+        {
+            let sql = format!(
+                "SELECT {} FROM Multimedia m JOIN Publication p ON p.{} = m.MultimediaId LIMIT 1",
+                file_column, cover_column
+            );
+            let cover: Option<String> = self.conn
+                .query_row(&sql, [], |row| row.get(0))
+                .optional()?
+                .flatten();
+            if cover.is_some() {
+                return Ok(cover);
+            }
+        }
+
+        let sql = format!(
+            "SELECT {} FROM Multimedia m WHERE lower({}) LIKE '%\\_cvr%' ESCAPE '\\' ORDER BY m.rowid LIMIT 1",
+            file_column, file_column
+        );
+        Ok(self.conn.query_row(&sql, [], |row| row.get(0)).optional()?.flatten())
+    }
+
+    /// Retrieves images stored as (encrypted) blobs inside the `Multimedia` or `Image` tables
+    /// instead of loose files in the archive. Returns `(name, encrypted_bytes)`; rows without a
+    /// file name are named after their table and rowid.
+    pub fn get_image_blobs(&self) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut blobs = Vec::new();
+        for table in ["Multimedia", "Image"] {
+            let columns = self.table_columns(table)?;
+            let data_column = match Self::required_column(&columns, table, "t", &["Data", "Content", "Blob", "ImageData"]) {
+                Ok(data_column) => data_column,
+                Err(_) => continue,
+            };
+
+            let sql = format!(
+                "SELECT t.rowid, {}, {} FROM {} t WHERE {} IS NOT NULL",
+                Self::column_or_null(&columns, "t", &["FilePath", "FileName", "Name"]),
+                data_column,
+                table,
+                data_column,
+            );
+            let mut stmt = self.conn.prepare(&sql)?;
+
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    Self::image_bytes(row.get_ref(2)?),
+                ))
+            })?;
+
+            for row in rows {
+                let (rowid, name, data) = row?;
+                let name = name.unwrap_or_else(|| format!("{}-{}", table.to_lowercase(), rowid));
+                let data = match data {
+                    Some(data) if !data.is_empty() => data,
+                    Some(_) => continue,
+                    None => {
+                        log::warn!("Skipping image '{}' in {}: data is neither a BLOB nor base64 text", name, table);
+                        continue;
+                    }
+                };
+                blobs.push((name, data));
+            }
+        }
+
+        Ok(blobs)
+    }
+
+    /// Retrieves the raw encrypted verses of a chapter from the `BibleChapter`/`BibleVerse`
+    /// tables of study Bibles. The chapter row gives the range of verse ids; the verse number
+    /// is read from the label when it holds one, and otherwise counted from the chapter start.
+    /// Returns an empty list when the publication has no such tables or chapter.
+    pub fn get_bible_verses(&self, book: u32, chapter: u32) -> Result<Vec<VerseRow>> {
+        let chapter_columns = self.table_columns("BibleChapter")?;
+        let verse_columns = self.table_columns("BibleVerse")?;
+        if chapter_columns.is_empty() || verse_columns.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let sql = format!(
+            "SELECT {}, {} FROM BibleChapter c WHERE {} = ? AND {} = ? LIMIT 1",
+            Self::required_column(&chapter_columns, "BibleChapter", "c", &["FirstVerseId"])?,
+            Self::required_column(&chapter_columns, "BibleChapter", "c", &["LastVerseId"])?,
+            Self::required_column(&chapter_columns, "BibleChapter", "c", &["BookNumber", "Book"])?,
+            Self::required_column(&chapter_columns, "BibleChapter", "c", &["ChapterNumber", "Chapter"])?,
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut rows = stmt.query([book, chapter])?;
+        let (first_verse, last_verse): (i64, i64) = match rows.next()? {
+            Some(row) => (row.get(0)?, row.get(1)?),
+            None => return Ok(Vec::new()),
+        };
+
+        let id_column = Self::required_column(&verse_columns, "BibleVerse", "v", &["BibleVerseId"])?;
+        let sql = format!(
+            "SELECT {}, {}, {} FROM BibleVerse v WHERE {} BETWEEN ? AND ? ORDER BY {}",
+            id_column,
+            Self::column_or_null(&verse_columns, "v", &["Label"]),
+            Self::required_column(&verse_columns, "BibleVerse", "v", &["Content"])?,
+            id_column,
+            id_column,
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+
+        let rows = stmt.query_map([first_verse, last_verse], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                Self::content_bytes(row.get_ref(2)?),
+            ))
+        })?;
+
+        let mut verses = Vec::new();
+        for row in rows {
+            let (id, label, content) = row?;
+            let verse_number = label
+                .as_deref()
+                .and_then(Self::verse_number_from_label)
+                .unwrap_or((id - first_verse + 1) as u32);
+
+            verses.push(VerseRow { verse_number, label, content });
+        }
+
+        Ok(verses)
+    }
+
+    /// First run of digits in a verse label such as `<span class="vl">16 </span>`
+    fn verse_number_from_label(label: &str) -> Option<u32> {
+        let mut text = label;
+        // Skip markup so digits inside attributes are not picked up
+        let mut plain = String::new();
+        while let Some(start) = text.find('<') {
+            plain.push_str(&text[..start]);
+            text = text[start..].find('>').map(|end| &text[start + end + 1..]).unwrap_or("");
+        }
+        plain.push_str(text);
+
+        let digits: String = plain
+            .chars()
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        digits.parse().ok()
+    }
+
+    /// Ciphertext of a `Content` column, empty for NULL. Some schema variants store it base64
+    /// encoded, usually in a TEXT column; that form is decoded so it decrypts like a BLOB.
+    fn content_bytes(value: ValueRef) -> Vec<u8> {
+        match value {
+            ValueRef::Blob(bytes) | ValueRef::Text(bytes) => {
+                Self::decode_base64_content(bytes).unwrap_or_else(|| bytes.to_vec())
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Ciphertext of an image data column. Some schema variants store it base64 encoded in a
+    /// TEXT column; `None` when the value is neither that nor a BLOB.
+    fn image_bytes(value: ValueRef) -> Option<Vec<u8>> {
+        match value {
+            ValueRef::Blob(bytes) => Some(bytes.to_vec()),
+            ValueRef::Text(bytes) => Self::decode_base64_content(bytes),
+            _ => None,
+        }
+    }
+
+    /// Decodes content made only of base64 characters. Raw ciphertext is never plain ASCII,
+    /// and a decoded value must still be whole AES blocks to be taken as ciphertext.
+    fn decode_base64_content(bytes: &[u8]) -> Option<Vec<u8>> {
+        let looks_like_base64 = bytes
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'=') || b.is_ascii_whitespace());
+        if bytes.is_empty() || !looks_like_base64 {
+            return None;
+        }
+
+        let compact: Vec<u8> = bytes.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+        let decoded = general_purpose::STANDARD.decode(compact).ok()?;
+        (!decoded.is_empty() && decoded.len() % 16 == 0).then_some(decoded)
+    }
+
+    /// Text value of a loosely typed column; numeric codes are not meaningful on their own
+    fn text_or_none(value: ValueRef) -> Option<String> {
+        match value {
+            ValueRef::Text(text) => Some(String::from_utf8_lossy(text).into_owned()),
+            _ => None,
+        }
+    }
+
+    /// Runs a custom read-only statement and returns every row as a list of column values.
+    /// Statements that would modify the database are rejected.
+    pub fn query_raw<P: rusqlite::Params>(&self, sql: &str, params: P) -> Result<Vec<Vec<Value>>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        if !stmt.readonly() {
+            return Err(anyhow!("Only read-only statements are allowed: {}", sql));
+        }
+
+        let column_count = stmt.column_count();
+        let rows = stmt.query_map(params, |row| {
+            (0..column_count).map(|index| row.get::<_, Value>(index)).collect()
+        })?;
+
+        let mut values = Vec::new();
+        for row in rows {
+            values.push(row?);
+        }
+
+        Ok(values)
+    }
+
+    /// Gives access to the underlying connection for queries the crate does not model.
+    /// The connection is read-only, so writes fail, unless the service was built with
+    /// `from_bytes_writable`.
+    pub fn with_connection<T>(&self, f: impl FnOnce(&Connection) -> rusqlite::Result<T>) -> Result<T> {
+        Ok(f(&self.conn)?)
+    }
+
+    /// Lower-cased column names of a table (empty when the table does not exist)
+    fn table_columns(&self, table: &str) -> Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(1))?;
+
+        let mut columns = HashSet::new();
+        for name in rows {
+            columns.insert(name?.to_lowercase());
+        }
+
+        Ok(columns)
+    }
+
+    /// Picks the first candidate column present in the table, failing with an error naming it otherwise
+    fn required_column(columns: &HashSet<String>, table: &str, alias: &str, candidates: &[&str]) -> Result<String> {
+        candidates
+            .iter()
+            .find(|name| columns.contains(&name.to_lowercase()))
+            .map(|name| format!("{}.{}", alias, name))
+            .ok_or_else(|| anyhow!("{} table has no {} column", table, candidates.join(" or ")))
+    }
+
+    /// Picks the first candidate column present in the table, or `NULL` so the query still runs
+    fn column_or_null(columns: &HashSet<String>, alias: &str, candidates: &[&str]) -> String {
+        candidates
+            .iter()
+            .find(|name| columns.contains(&name.to_lowercase()))
+            .map(|name| format!("{}.{}", alias, name))
+            .unwrap_or_else(|| "NULL".to_string())
+    }
+}
+
+/// Iterator returned by `DatabaseService::documents_iter`. Each item reads, decrypts and
+/// parses a single document.
+pub struct DocumentsIter<'a> {
+    db: &'a DatabaseService,
+    crypto: &'a CryptoService,
+    key: &'a [u8],
+    iv: &'a [u8],
+    class_id: i32,
+    policy: RewritePolicy,
+    encryption: EncryptionMode,
+    ids: std::vec::IntoIter<u32>,
+}
+
+impl DocumentsIter<'_> {
+    /// Rewrites image sources with `policy` instead of `RewritePolicy::LocalizeAll`
+    pub fn rewrite_policy(mut self, policy: RewritePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Reads the content as `encryption` dictates instead of `EncryptionMode::Auto`
+    pub fn encryption(mut self, encryption: EncryptionMode) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    fn load(&self, doc_id: u32) -> crate::Result<Option<Document>> {
+        let row = match self.db.get_document(doc_id).map_err(JwpubError::Database)? {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        let html_raw = self.crypto.read_content(&row.content, self.key, self.iv, self.encryption)
+            .map_err(|source| JwpubError::DecryptionFailed { doc_id, source })?;
+
+        Ok(Some(crate::build_document(self.class_id, &row, &html_raw, self.policy)))
+    }
+}
+
+impl Iterator for DocumentsIter<'_> {
+    type Item = crate::Result<Document>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // A document deleted since the ids were listed is skipped
+        loop {
+            let doc_id = self.ids.next()?;
+            if let Some(result) = self.load(doc_id).transpose() {
+                return Some(result);
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.ids.size_hint().1)
+    }
 }
@@ -1,13 +1,25 @@
 use anyhow::{Result, anyhow};
+use futures_util::stream::{self, StreamExt};
+use md5::Md5;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use std::fs::File;
-use std::io::copy;
-use std::path::Path;
+use std::io::{copy, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+pub use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 
 const JW_CDN_API: &str = "https://b.jw-cdn.org/apis/pub-media/GETPUBMEDIALINKS?";
 
 #[derive(Debug, Deserialize)]
 pub struct ApiResponse {
+    #[serde(rename = "pubName", default)]
+    pub pub_name: Option<String>,
+    /// Issue tag of the returned files (empty for undated publications such as books)
+    #[serde(default)]
+    pub issue: Option<String>,
     pub files: std::collections::HashMap<String, LanguageFiles>,
 }
 
@@ -15,16 +27,244 @@ pub struct ApiResponse {
 #[serde(rename_all = "UPPERCASE")]
 pub struct LanguageFiles {
     pub jwpub: Option<Vec<PublicationFile>>,
+    pub epub: Option<Vec<PublicationFile>>,
+    pub pdf: Option<Vec<PublicationFile>>,
+    pub brl: Option<Vec<PublicationFile>>,
+    pub rtf: Option<Vec<PublicationFile>>,
+    pub mp3: Option<Vec<PublicationFile>>,
+    pub mp4: Option<Vec<PublicationFile>>,
+}
+
+impl LanguageFiles {
+    /// Files offered in the given format, if any
+    pub fn files_for(&self, format: FileFormat) -> Option<&Vec<PublicationFile>> {
+        match format {
+            FileFormat::Jwpub => self.jwpub.as_ref(),
+            FileFormat::Epub => self.epub.as_ref(),
+            FileFormat::Pdf => self.pdf.as_ref(),
+            FileFormat::Brl => self.brl.as_ref(),
+            FileFormat::Rtf => self.rtf.as_ref(),
+            FileFormat::Mp3 => self.mp3.as_ref(),
+            FileFormat::Mp4 => self.mp4.as_ref(),
+        }
+    }
+}
+
+/// File formats served by the CDN for publications and their audio/video media
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileFormat {
+    #[default]
+    Jwpub,
+    Epub,
+    Pdf,
+    Brl,
+    Rtf,
+    Mp3,
+    Mp4,
+}
+
+impl FileFormat {
+    /// Value of the `fileformat` query parameter (also the key in `LanguageFiles`)
+    pub fn as_api_str(&self) -> &'static str {
+        match self {
+            FileFormat::Jwpub => "JWPUB",
+            FileFormat::Epub => "EPUB",
+            FileFormat::Pdf => "PDF",
+            FileFormat::Brl => "BRL",
+            FileFormat::Rtf => "RTF",
+            FileFormat::Mp3 => "MP3",
+            FileFormat::Mp4 => "MP4",
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct PublicationFile {
+    #[serde(default)]
+    pub title: Option<String>,
     pub file: FileInfo,
+    pub filesize: Option<u64>,
+    /// Track number of songs and videos (0 for whole publications)
+    #[serde(default)]
+    pub track: Option<u32>,
+    /// Resolution of videos (e.g. `720p`)
+    #[serde(default)]
+    pub label: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+impl PublicationFile {
+    /// File info with the size reported next to it in the API response filled in
+    pub fn file_info(&self) -> FileInfo {
+        FileInfo {
+            expected_size: self.filesize.or(self.file.expected_size),
+            ..self.file.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct FileInfo {
     pub url: String,
+    /// Size in bytes announced by the CDN
+    #[serde(skip)]
+    pub expected_size: Option<u64>,
+    /// Hex digest announced by the CDN (MD5, or SHA-256 when 64 characters long)
+    #[serde(rename = "checksum", default)]
+    pub expected_hash: Option<String>,
+    /// Last modification date reported by the CDN (e.g. `2022-11-29 10:02:43`)
+    #[serde(rename = "modifiedDatetime", default)]
+    pub modified: Option<String>,
+}
+
+impl FileInfo {
+    /// Checks a downloaded file against the expected size and hash, when known
+    pub fn verify(&self, path: &Path) -> Result<()> {
+        let mut checksum = self.checksum();
+        let size = copy(&mut File::open(path)?, &mut checksum)?;
+        self.check(path, size, checksum)
+    }
+
+    /// Hasher matching `expected_hash`: SHA-256 for 64 hex characters, MD5 otherwise
+    fn checksum(&self) -> Checksum {
+        match self.expected_hash.as_deref().filter(|h| !h.is_empty()) {
+            Some(hash) if hash.len() == 64 => Checksum::Sha256(Sha256::new()),
+            Some(_) => Checksum::Md5(Md5::new()),
+            None => Checksum::None,
+        }
+    }
+
+    /// Compares `size` bytes hashed into `checksum` with what the CDN announced
+    fn check(&self, path: &Path, size: u64, checksum: Checksum) -> Result<()> {
+        if let Some(expected_size) = self.expected_size {
+            if size != expected_size {
+                return Err(anyhow!(
+                    "Size mismatch for {:?}: expected {} bytes, got {}",
+                    path, expected_size, size
+                ));
+            }
+        }
+
+        if let (Some(expected_hash), Some(actual_hash)) = (self.expected_hash.as_deref(), checksum.finalize()) {
+            if !actual_hash.eq_ignore_ascii_case(expected_hash) {
+                return Err(anyhow!(
+                    "Checksum mismatch for {:?}: expected {}, got {}",
+                    path, expected_hash, actual_hash
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Running digest of a download, fed as the bytes are written
+enum Checksum {
+    None,
+    Md5(Md5),
+    Sha256(Sha256),
+}
+
+impl Checksum {
+    /// Hex digest, `None` when there is nothing to compare against
+    fn finalize(self) -> Option<String> {
+        match self {
+            Checksum::None => None,
+            Checksum::Md5(hasher) => Some(hex::encode(hasher.finalize())),
+            Checksum::Sha256(hasher) => Some(hex::encode(hasher.finalize())),
+        }
+    }
+}
+
+impl Write for Checksum {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Checksum::None => {}
+            Checksum::Md5(hasher) => hasher.update(buf),
+            Checksum::Sha256(hasher) => hasher.update(buf),
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// An issue of a publication available on the CDN
+#[derive(Debug, Clone)]
+pub struct IssueInfo {
+    /// Issue tag (e.g. `20230100`), `None` for publications without issues
+    pub tag: Option<String>,
+    pub title: String,
+    pub url: String,
+}
+
+/// Metadata of a publication file resolved on the CDN without downloading it
+#[derive(Debug, Clone)]
+pub struct ResolvedPublication {
+    pub url: String,
+    pub file_size: Option<u64>,
+    pub modified: Option<String>,
+}
+
+/// A publication to fetch with `DiscoveryService::download_many`
+#[derive(Debug, Clone)]
+pub struct DownloadRequest {
+    pub pub_name: String,
+    pub lang: String,
+    pub issue: String,
+    /// Directory the file is saved into, under the name it has on the CDN
+    pub dest_dir: PathBuf,
+}
+
+/// `User-Agent` sent unless `DownloadConfig::user_agent` says otherwise
+pub const DEFAULT_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Retry, timeout and header settings for the network calls of `DiscoveryService`
+#[derive(Debug, Clone)]
+pub struct DownloadConfig {
+    /// Extra attempts after the first one fails with a connection, timeout or 5xx error
+    pub retries: u32,
+    /// Delay before the first retry; doubled after each further attempt
+    pub base_delay: Duration,
+    /// Limit for establishing the connection
+    pub connect_timeout: Duration,
+    /// Limit for waiting on the response and on each read of the body, so a stalled
+    /// transfer fails instead of hanging while a slow but steady one still completes
+    pub read_timeout: Duration,
+    /// Sent with every request, e.g. a `Proxy-Authorization` header
+    pub headers: HeaderMap,
+    /// `User-Agent` of every request. Defaults to `jw_parser/<version>`.
+    pub user_agent: String,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            base_delay: Duration::from_millis(500),
+            connect_timeout: Duration::from_secs(30),
+            read_timeout: Duration::from_secs(30),
+            headers: HeaderMap::new(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+        }
+    }
+}
+
+/// Failure of a single attempt, telling whether it is worth retrying
+enum AttemptError {
+    Transient(anyhow::Error),
+    Permanent(anyhow::Error),
+}
+
+impl From<reqwest::Error> for AttemptError {
+    fn from(e: reqwest::Error) -> Self {
+        let transient = match e.status() {
+            Some(status) => status.is_server_error(),
+            None => e.is_connect() || e.is_timeout() || e.is_body(),
+        };
+        if transient { AttemptError::Transient(e.into()) } else { AttemptError::Permanent(e.into()) }
+    }
 }
 
 pub struct DiscoveryService;
@@ -32,31 +272,294 @@ pub struct DiscoveryService;
 impl DiscoveryService {
     /// Discovers and returns the URL for a specific publication and issue
     pub fn find_url(pub_name: &str, lang: &str, issue: &str) -> Result<String> {
-        let url = format!(
-            "{}langwritten={}&pub={}&issue={}&output=json&fileformat=JWPUB",
-            JW_CDN_API, lang, pub_name, issue
-        );
+        Self::find_url_with_format(pub_name, lang, issue, FileFormat::Jwpub)
+    }
 
-        let response: ApiResponse = reqwest::blocking::get(url)?.json()?;
-        
-        let lang_files = response.files.get(lang)
-            .ok_or_else(|| anyhow!("No files found for language {}", lang))?;
+    /// Same as `find_url` for any format offered by the CDN (EPUB, PDF, ...)
+    pub fn find_url_with_format(pub_name: &str, lang: &str, issue: &str, format: FileFormat) -> Result<String> {
+        Ok(Self::find_file_with_format(pub_name, lang, issue, format)?.url)
+    }
+
+    /// Like `find_url`, but keeps the size and checksum announced by the CDN
+    pub fn find_file(pub_name: &str, lang: &str, issue: &str) -> Result<FileInfo> {
+        Self::find_file_with_format(pub_name, lang, issue, FileFormat::Jwpub)
+    }
+
+    /// Like `find_url_with_format`, but keeps the size and checksum announced by the CDN
+    pub fn find_file_with_format(pub_name: &str, lang: &str, issue: &str, format: FileFormat) -> Result<FileInfo> {
+        Self::find_file_with_config(pub_name, lang, issue, format, &DownloadConfig::default())
+    }
+
+    /// Like `find_file_with_format`, with explicit retry and timeout settings
+    pub fn find_file_with_config(pub_name: &str, lang: &str, issue: &str, format: FileFormat, config: &DownloadConfig) -> Result<FileInfo> {
+        let url = Self::api_url(pub_name, lang, Some(issue), None, format);
+        let response = Self::fetch_api(&url, config)?;
+        Self::first_file(response, lang, format)
+    }
+
+    /// Resolves an audio or video file (`FileFormat::Mp3`/`Mp4`) of a publication, e.g. one
+    /// song of a songbook with `track`. `issue` is only needed for dated media. When several
+    /// files match (video resolutions), the first one listed by the CDN is returned.
+    pub fn find_media_url(pub_name: &str, lang: &str, track: Option<u32>, issue: Option<&str>, format: FileFormat) -> Result<String> {
+        let url = Self::api_url(pub_name, lang, issue, track, format);
+        let response = Self::fetch_api(&url, &DownloadConfig::default())?;
 
-        let jwpub_list = lang_files.jwpub.as_ref()
-            .ok_or_else(|| anyhow!("No JWPUB files found"))?;
+        let files = response.files.get(lang)
+            .ok_or_else(|| anyhow!("No files found for language {}", lang))?
+            .files_for(format)
+            .ok_or_else(|| anyhow!("Format {} is not offered for this publication", format.as_api_str()))?;
 
-        let file_url = jwpub_list.first()
-            .ok_or_else(|| anyhow!("Empty JWPUB list"))?
-            .file.url.clone();
+        // The CDN may list every track even when one is requested
+        let file = files
+            .iter()
+            .find(|file| track.is_none() || file.track.is_none() || file.track == track)
+            .ok_or_else(|| anyhow!("No {} file found for track {:?}", format.as_api_str(), track))?;
+
+        Ok(file.file.url.clone())
+    }
+
+    /// Dry run: checks that a publication exists and reports its size and modification date
+    /// without downloading it
+    pub fn resolve(pub_name: &str, lang: &str, issue: &str) -> Result<ResolvedPublication> {
+        let file_info = Self::find_file(pub_name, lang, issue)?;
+        Ok(ResolvedPublication {
+            url: file_info.url,
+            file_size: file_info.expected_size,
+            modified: file_info.modified,
+        })
+    }
+
+    /// Lists the issues the CDN offers for a publication when no issue is requested, oldest
+    /// first. Each issue is read from its file name (`w_E_202301.jwpub` is `20230100`).
+    /// Publications without issues (books, brochures) yield a single entry with no tag.
+    pub fn list_issues(pub_name: &str, lang: &str) -> Result<Vec<IssueInfo>> {
+        let url = Self::api_url(pub_name, lang, None, None, FileFormat::Jwpub);
+        let response = Self::fetch_api(&url, &DownloadConfig::default())?;
+
+        // Only meaningful for the file the response describes, when its name carries no date
+        let response_tag = response.issue.clone().filter(|issue| !issue.is_empty() && issue != "0");
+        let default_title = response.pub_name.clone().unwrap_or_else(|| pub_name.to_string());
+
+        let jwpub_list = response.files.get(lang)
+            .and_then(|lang_files| lang_files.jwpub.as_ref())
+            .ok_or_else(|| anyhow!("No JWPUB files found for {} in language {}", pub_name, lang))?;
+
+        let mut issues: Vec<IssueInfo> = Vec::new();
+        for entry in jwpub_list {
+            let tag = Self::issue_from_url(&entry.file.url).or_else(|| response_tag.clone());
+            if issues.iter().any(|issue| issue.tag == tag) { continue; }
+
+            issues.push(IssueInfo {
+                tag,
+                title: entry.title.clone().unwrap_or_else(|| default_title.clone()),
+                url: entry.file.url.clone(),
+            });
+        }
+        issues.sort_by(|a, b| a.tag.cmp(&b.tag));
+
+        Ok(issues)
+    }
 
-        Ok(file_url)
+    /// Issue tag from the date at the end of a file name: `_YYYYMM` becomes `YYYYMM00`,
+    /// `_YYYYMMDD` is kept as is
+    fn issue_from_url(url: &str) -> Option<String> {
+        let file_name = url.split(['?', '#']).next()?.rsplit('/').next()?;
+        let stem = file_name.split('.').next()?;
+        let date = stem.rsplit('_').next().filter(|date| date.bytes().all(|b| b.is_ascii_digit()))?;
+        match date.len() {
+            6 => Some(format!("{}00", date)),
+            8 => Some(date.to_string()),
+            _ => None,
+        }
     }
 
-    /// Downloads a file from a URL to a local path
+    /// Downloads a file from a URL to a local path, retrying transient failures with the
+    /// default `DownloadConfig`.
+    /// Fails if fewer bytes than the announced Content-Length were received. Nothing else is
+    /// checked: when the file came from `find_file`, use `download_verified` instead.
     pub fn download_file(url: &str, dest_path: &Path) -> Result<()> {
-        let mut response = reqwest::blocking::get(url)?;
-        let mut file = File::create(dest_path)?;
-        copy(&mut response, &mut file)?;
+        Self::download_file_with_config(url, dest_path, &DownloadConfig::default())
+    }
+
+    /// Like `download_file`, with explicit retry and timeout settings
+    pub fn download_file_with_config(url: &str, dest_path: &Path, config: &DownloadConfig) -> Result<()> {
+        let client = Self::client(config)?;
+        Self::with_retries(config, url, || {
+            let mut response = client.get(url).send()?.error_for_status()?;
+            let content_length = response.content_length();
+            let mut file = File::create(dest_path).map_err(|e| AttemptError::Permanent(e.into()))?;
+            // A broken connection surfaces as an I/O error while copying the body
+            let written = copy(&mut response, &mut file).map_err(|e| AttemptError::Transient(e.into()))?;
+
+            if let Some(content_length) = content_length {
+                if written != content_length {
+                    return Err(AttemptError::Transient(anyhow!(
+                        "Truncated download from {}: expected {} bytes, got {}",
+                        url, content_length, written
+                    )));
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Downloads a file and verifies it against the size and checksum from the CDN
+    pub fn download_verified(file_info: &FileInfo, dest_path: &Path) -> Result<()> {
+        Self::download_verified_with_config(file_info, dest_path, &DownloadConfig::default())
+    }
+
+    /// Like `download_verified`, with explicit retry and timeout settings
+    pub fn download_verified_with_config(file_info: &FileInfo, dest_path: &Path, config: &DownloadConfig) -> Result<()> {
+        Self::download_file_with_config(&file_info.url, dest_path, config)?;
+        file_info.verify(dest_path)
+    }
+
+    /// Non-blocking version of `find_url`, suitable for use inside a tokio runtime
+    pub async fn find_url_async(pub_name: &str, lang: &str, issue: &str) -> Result<String> {
+        Ok(Self::find_file_async(pub_name, lang, issue).await?.url)
+    }
+
+    /// Non-blocking version of `find_file`
+    pub async fn find_file_async(pub_name: &str, lang: &str, issue: &str) -> Result<FileInfo> {
+        let url = Self::api_url(pub_name, lang, Some(issue), None, FileFormat::Jwpub);
+        let client = Self::async_client(&DownloadConfig::default())?;
+        let response: ApiResponse = client.get(url).send().await?.json().await?;
+        Self::first_file(response, lang, FileFormat::Jwpub)
+    }
+
+    /// Non-blocking version of `download_file`. The body is streamed to disk chunk by chunk.
+    pub async fn download_file_async(url: &str, dest_path: &Path) -> Result<()> {
+        Self::stream_to_file(url, dest_path, std::io::sink()).await?;
         Ok(())
     }
+
+    /// Non-blocking version of `download_verified`. The checksum is computed while the body
+    /// is streamed, so the file is not read back.
+    pub async fn download_verified_async(file_info: &FileInfo, dest_path: &Path) -> Result<()> {
+        let mut checksum = file_info.checksum();
+        let size = Self::stream_to_file(&file_info.url, dest_path, &mut checksum).await?;
+        file_info.check(dest_path, size, checksum)
+    }
+
+    /// Streams the body of `url` to `dest_path`, copying every chunk to `tee` as well.
+    /// Returns the number of bytes written.
+    async fn stream_to_file<W: Write>(url: &str, dest_path: &Path, mut tee: W) -> Result<u64> {
+        let client = Self::async_client(&DownloadConfig::default())?;
+        let mut response = client.get(url).send().await?.error_for_status()?;
+        let mut file = tokio::fs::File::create(dest_path).await?;
+
+        let mut written = 0;
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await?;
+            tee.write_all(&chunk)?;
+            written += chunk.len() as u64;
+        }
+        file.flush().await?;
+
+        Ok(written)
+    }
+
+    /// Queries the CDN API, retrying transient failures
+    fn fetch_api(url: &str, config: &DownloadConfig) -> Result<ApiResponse> {
+        let client = Self::client(config)?;
+        Self::with_retries(config, url, || Ok(client.get(url).send()?.error_for_status()?.json()?))
+    }
+
+    fn client(config: &DownloadConfig) -> Result<reqwest::blocking::Client> {
+        Ok(reqwest::blocking::Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.read_timeout)
+            .user_agent(config.user_agent.as_str())
+            .default_headers(config.headers.clone())
+            .build()?)
+    }
+
+    fn async_client(config: &DownloadConfig) -> Result<reqwest::Client> {
+        Ok(reqwest::Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .read_timeout(config.read_timeout)
+            .user_agent(config.user_agent.as_str())
+            .default_headers(config.headers.clone())
+            .build()?)
+    }
+
+    /// Runs `attempt` until it succeeds, fails permanently, or `config.retries` is exhausted,
+    /// doubling the delay between attempts
+    fn with_retries<T>(config: &DownloadConfig, url: &str, mut attempt: impl FnMut() -> std::result::Result<T, AttemptError>) -> Result<T> {
+        let mut delay = config.base_delay;
+        let mut retry = 0;
+        loop {
+            match attempt() {
+                Ok(value) => return Ok(value),
+                Err(AttemptError::Transient(e)) if retry < config.retries => {
+                    retry += 1;
+                    log::warn!("Request to {} failed ({}), retry {}/{} in {:?}", url, e, retry, config.retries, delay);
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+                Err(AttemptError::Transient(e)) | Err(AttemptError::Permanent(e)) => return Err(e),
+            }
+        }
+    }
+
+    /// Downloads many publications concurrently, with at most `concurrency` requests in flight.
+    /// Each file is verified against the size and checksum announced by the CDN. Results (the
+    /// saved file paths) come back in the same order as `requests`.
+    pub async fn download_many(requests: &[DownloadRequest], concurrency: usize) -> Vec<Result<PathBuf>> {
+        stream::iter(requests)
+            .map(Self::download_request)
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    async fn download_request(request: &DownloadRequest) -> Result<PathBuf> {
+        let file_info = Self::find_file_async(&request.pub_name, &request.lang, &request.issue).await?;
+        let url = &file_info.url;
+        let parsed = url::Url::parse(url)?;
+        let file_name = parsed
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| anyhow!("Cannot derive a file name from {}", url))?;
+
+        let dest_path = request.dest_dir.join(file_name);
+        Self::download_verified_async(&file_info, &dest_path).await?;
+        Ok(dest_path)
+    }
+
+    fn api_url(pub_name: &str, lang: &str, issue: Option<&str>, track: Option<u32>, format: FileFormat) -> String {
+        let issue = issue.map(|issue| format!("&issue={}", issue)).unwrap_or_default();
+        let track = track.map(|track| format!("&track={}", track)).unwrap_or_default();
+        format!(
+            "{}langwritten={}&pub={}{}{}&output=json&fileformat={}",
+            JW_CDN_API, lang, pub_name, issue, track, format.as_api_str()
+        )
+    }
+
+    fn first_file(response: ApiResponse, lang: &str, format: FileFormat) -> Result<FileInfo> {
+        let lang_files = response.files.get(lang)
+            .ok_or_else(|| anyhow!("No files found for language {}", lang))?;
+
+        let file_list = lang_files.files_for(format)
+            .ok_or_else(|| anyhow!("Format {} is not offered for this publication", format.as_api_str()))?;
+
+        let file_info = file_list.first()
+            .ok_or_else(|| anyhow!("Empty {} list", format.as_api_str()))?
+            .file_info();
+
+        Ok(file_info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn issue_tags_come_from_the_file_name() {
+        assert_eq!(DiscoveryService::issue_from_url("https://cfp2.jw-cdn.org/a/1/w_E_202301.jwpub").as_deref(), Some("20230100"));
+        assert_eq!(DiscoveryService::issue_from_url("https://cdn/w_E_20230115.jwpub?x=1").as_deref(), Some("20230115"));
+        assert_eq!(DiscoveryService::issue_from_url("https://cdn/lff_E.jwpub"), None);
+    }
 }
@@ -0,0 +1,73 @@
+use thiserror::Error;
+
+/// Errors returned by the public parse entry points
+#[derive(Debug, Error)]
+pub enum JwpubError {
+    /// The file (or its inner `contents` archive) is not a readable ZIP
+    #[error("Not a valid ZIP archive: {0}")]
+    NotAZip(#[source] zip::result::ZipError),
+
+    /// The file is not a JWPUB at all; `detected` names what it looks like (`epub`, `pdf`, ...)
+    #[error("Not a JWPUB file (detected: {detected})")]
+    NotAJwpub { detected: String },
+
+    /// The JWPUB has no `contents` entry, nor any other entry holding a ZIP with a database.
+    /// `entries` lists what the archive does contain.
+    #[error("'contents' file not found in JWPUB (entries: {})", .entries.join(", "))]
+    ContentsMissing { entries: Vec<String> },
+
+    /// The `contents` archive has no SQLite database
+    #[error("Database file not found in contents")]
+    DatabaseMissing,
+
+    /// The database could not be loaded or queried (e.g. no publication data)
+    #[error("Database error: {0}")]
+    Database(#[source] anyhow::Error),
+
+    /// The master key could not be decoded
+    #[error("Key derivation failed: {0}")]
+    KeyDerivation(#[source] KeyError),
+
+    /// The keys derived from the pub card do not decrypt the publication's documents
+    #[error("Derived key appears incorrect, check the pub card fields ({pub_card})")]
+    KeyMismatch { pub_card: String },
+
+    /// A document could not be decrypted or inflated
+    #[error("Failed to decrypt document {doc_id}: {source}")]
+    DecryptionFailed {
+        doc_id: u32,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    /// An entry of the archive could not be read
+    #[error("Failed to read archive entry: {0}")]
+    Archive(#[from] zip::result::ZipError),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// Any other failure, e.g. raised by a custom `AssetSink`
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Why a master key given to the `CryptoService` constructors was rejected
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum KeyError {
+    /// The outer base64 layer does not decode
+    #[error("Master key is not valid base64: {0}")]
+    InvalidBase64(String),
+
+    /// The base64 payload is not UTF-8 text
+    #[error("Master key is not valid UTF-8 once base64-decoded")]
+    NotUtf8,
+
+    /// The decoded text is not a hex string
+    #[error("Master key is not valid hex: {0}")]
+    InvalidHex(String),
+
+    /// The raw key does not have the length the derivation needs
+    #[error("Invalid master key length: expected {expected} bytes, got {got}")]
+    WrongLength { got: usize, expected: usize },
+}
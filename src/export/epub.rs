@@ -0,0 +1,208 @@
+use ego_tree::NodeRef;
+use scraper::{Html, Node};
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::escape_html;
+use crate::assets;
+use crate::html::HtmlParser;
+use crate::models::{AssetType, Manifest};
+
+/// Elements serialized as `<x/>` in XHTML
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+/// Writes the manifest as an EPUB 3 book at `out`.
+///
+/// Every document becomes a chapter, in manifest order, titled after `Document::title`.
+/// Images referenced by the documents are copied from `assets_dir` into `OEBPS/images/`
+/// and their `./assets/` paths rewritten accordingly.
+pub fn write_epub(manifest: &Manifest, assets_dir: &Path, out: &Path) -> crate::Result<()> {
+    let mut zip = ZipWriter::new(File::create(out)?);
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // The mimetype must be the first entry, uncompressed
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(CONTAINER_XML.as_bytes())?;
+
+    // Images referenced by any document that actually exist on disk
+    let image_names: BTreeSet<&str> = manifest
+        .documents
+        .iter()
+        .flat_map(|document| document.assets.iter())
+        .filter(|asset| asset.r#type == AssetType::Image)
+        .map(|asset| asset.file_name.as_str())
+        .filter(|name| assets_dir.join(name).is_file())
+        .collect();
+
+    for name in &image_names {
+        zip.start_file(format!("OEBPS/images/{}", name), stored)?;
+        zip.write_all(&fs::read(assets_dir.join(name))?)?;
+    }
+
+    for document in &manifest.documents {
+        let html = HtmlParser::rewrite_image_sources(&document.html, |src| {
+            src.strip_prefix("./assets/").map(|name| format!("images/{}", name))
+        });
+        zip.start_file(format!("OEBPS/{}", chapter_file(document.id)), deflated)?;
+        zip.write_all(chapter_xhtml(&document.title, &html, manifest.language_code()).as_bytes())?;
+    }
+
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(nav_xhtml(manifest).as_bytes())?;
+
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(content_opf(manifest, &image_names).as_bytes())?;
+
+    zip.finish()?;
+    Ok(())
+}
+
+fn chapter_file(doc_id: u32) -> String {
+    format!("doc-{}.xhtml", doc_id)
+}
+
+fn content_opf(manifest: &Manifest, image_names: &BTreeSet<&str>) -> String {
+    let mut items = String::new();
+    let mut spine = String::new();
+    for document in &manifest.documents {
+        let _ = writeln!(
+            items,
+            r#"    <item id="doc-{id}" href="{file}" media-type="application/xhtml+xml"/>"#,
+            id = document.id,
+            file = chapter_file(document.id)
+        );
+        let _ = writeln!(spine, r#"    <itemref idref="doc-{}"/>"#, document.id);
+    }
+    for (index, name) in image_names.iter().enumerate() {
+        let _ = writeln!(
+            items,
+            r#"    <item id="img-{}" href="images/{}" media-type="{}"/>"#,
+            index,
+            escape_html(name),
+            assets::mime_type_for(name)
+        );
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="pub-id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="pub-id">urn:jwpub:{symbol}:{issue}:{language}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:language>{language_code}</dc:language>
+    <meta property="dcterms:modified">{modified}</meta>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+{items}  </manifest>
+  <spine>
+{spine}  </spine>
+</package>
+"#,
+        symbol = escape_html(&manifest.publication),
+        issue = escape_html(&manifest.issue),
+        language = escape_html(&manifest.language),
+        language_code = manifest.language_code(),
+        title = escape_html(&manifest.title),
+        modified = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+        items = items,
+        spine = spine,
+    )
+}
+
+fn nav_xhtml(manifest: &Manifest) -> String {
+    let mut entries = String::new();
+    for document in &manifest.documents {
+        let _ = writeln!(
+            entries,
+            r#"      <li><a href="{}">{}</a></li>"#,
+            chapter_file(document.id),
+            escape_html(&document.title)
+        );
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head><title>{title}</title></head>
+<body>
+  <nav epub:type="toc">
+    <h1>{title}</h1>
+    <ol>
+{entries}    </ol>
+  </nav>
+</body>
+</html>
+"#,
+        title = escape_html(&manifest.title),
+        entries = entries,
+    )
+}
+
+fn chapter_xhtml(title: &str, html: &str, language: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    let mut body = String::new();
+    write_xhtml(*fragment.root_element(), &mut body);
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xml:lang="{language}">
+<head><meta charset="utf-8"/><title>{title}</title></head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        language = escape_html(language),
+        title = escape_html(title),
+        body = body,
+    )
+}
+
+/// Serializes HTML as well-formed XHTML (closed void elements, escaped text and attributes),
+/// which EPUB readers require
+fn write_xhtml(node: NodeRef<Node>, out: &mut String) {
+    for child in node.children() {
+        match child.value() {
+            Node::Text(text) => out.push_str(&escape_html(text)),
+            Node::Element(element) => {
+                let name = element.name();
+                out.push('<');
+                out.push_str(name);
+                for (attr, value) in element.attrs() {
+                    let _ = write!(out, " {}=\"{}\"", attr, escape_html(value));
+                }
+
+                if VOID_ELEMENTS.contains(&name) {
+                    out.push_str("/>");
+                } else {
+                    out.push('>');
+                    write_xhtml(child, out);
+                    let _ = write!(out, "</{}>", name);
+                }
+            }
+            _ => {}
+        }
+    }
+}
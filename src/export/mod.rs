@@ -0,0 +1,178 @@
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+#[cfg(feature = "native")]
+use std::fs;
+#[cfg(feature = "native")]
+use std::path::{Component, Path, PathBuf};
+
+#[cfg(feature = "native")]
+pub mod epub;
+
+use crate::assets::{self, InMemoryAsset};
+use crate::html::HtmlParser;
+use crate::models::{Document, Manifest, TextDirection};
+
+/// How parsed documents are written out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "native", derive(clap::ValueEnum))]
+pub enum ExportFormat {
+    /// `manifest.json` plus an `assets` directory
+    #[default]
+    Manifest,
+    /// One self-contained `<doc_id>.html` per document, with its images inlined as data URIs
+    StandaloneHtml,
+}
+
+/// Renders a document as a complete HTML page with every `./assets/...` image inlined as a
+/// `data:` URI. Images missing from `assets` keep their relative path.
+pub fn standalone_html(document: &Document, assets: &[InMemoryAsset]) -> String {
+    let by_name: HashMap<&str, &[u8]> = assets
+        .iter()
+        .map(|(name, bytes)| (name.as_str(), bytes.as_slice()))
+        .collect();
+
+    let body = HtmlParser::rewrite_image_sources(&document.html, |src| {
+        let file_name = src.strip_prefix("./assets/")?;
+        let bytes = by_name.get(file_name)?;
+        Some(format!(
+            "data:{};base64,{}",
+            assets::mime_type_for(file_name),
+            general_purpose::STANDARD.encode(bytes)
+        ))
+    });
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{}\n</body>\n</html>\n",
+        escape_html(&document.title),
+        body
+    )
+}
+
+/// Writes `standalone_html` for every document as `<output_dir>/<doc_id>.html`
+/// and returns the paths written
+#[cfg(feature = "native")]
+pub fn write_standalone_html<P: AsRef<Path>>(documents: &[Document], assets: &[InMemoryAsset], output_dir: P) -> crate::Result<Vec<PathBuf>> {
+    let output_dir = output_dir.as_ref();
+    fs::create_dir_all(output_dir)?;
+
+    let mut paths = Vec::new();
+    for document in documents {
+        let path = output_dir.join(format!("{}.html", document.id));
+        fs::write(&path, standalone_html(document, assets))?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+/// Renders every document of the manifest as one HTML page in reading order: a table of
+/// contents linking to `#doc-<id>`, then each document in its own `<section>` after a divider.
+/// `./assets/` image paths are rewritten to `assets_href`, e.g. `../assets`.
+pub fn combined_html(manifest: &Manifest, assets_href: &str) -> String {
+    let assets_href = assets_href.trim_end_matches('/');
+
+    let mut toc = String::new();
+    let mut sections = String::new();
+    for document in &manifest.documents {
+        let _ = writeln!(
+            toc,
+            "<li><a href=\"#doc-{}\">{}</a></li>",
+            document.id,
+            escape_html(&document.title)
+        );
+
+        let body = HtmlParser::rewrite_image_sources(&document.html, |src| {
+            let file_name = src.strip_prefix("./assets/")?;
+            Some(format!("{}/{}", assets_href, file_name))
+        });
+        let _ = writeln!(
+            sections,
+            "<hr class=\"document-divider\">\n<section class=\"document\" id=\"doc-{}\">\n{}\n</section>",
+            document.id,
+            body
+        );
+    }
+
+    let direction = match manifest.direction {
+        TextDirection::Ltr => "ltr",
+        TextDirection::Rtl => "rtl",
+    };
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"{}\" dir=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<nav id=\"toc\">\n<h1>{}</h1>\n<ol>\n{}</ol>\n</nav>\n{}</body>\n</html>\n",
+        manifest.language_code(),
+        direction,
+        escape_html(&manifest.title),
+        escape_html(&manifest.title),
+        toc,
+        sections
+    )
+}
+
+/// Writes `combined_html` to `out`, pointing the images at `assets_dir` relative to the
+/// directory of `out` so both keep sharing one assets folder
+#[cfg(feature = "native")]
+pub fn write_combined_html(manifest: &Manifest, assets_dir: &Path, out: &Path) -> crate::Result<()> {
+    let out_dir = out.parent().unwrap_or(Path::new(""));
+    fs::create_dir_all(out_dir)?;
+    fs::write(out, combined_html(manifest, &relative_href(out_dir, assets_dir)))?;
+    Ok(())
+}
+
+/// Path of `to` relative to the directory `from`, with `/` separators. Both are resolved
+/// lexically against the current directory, so neither has to exist.
+#[cfg(feature = "native")]
+fn relative_href(from: &Path, to: &Path) -> String {
+    let absolute = |path: &Path| {
+        let path = std::env::current_dir().map(|cwd| cwd.join(path)).unwrap_or_else(|_| path.to_path_buf());
+        let mut components = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => { components.pop(); }
+                component => components.push(component.as_os_str().to_string_lossy().into_owned()),
+            }
+        }
+        components
+    };
+    let from = absolute(from);
+    let to = absolute(to);
+
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let parts: Vec<&str> = std::iter::repeat_n("..", from.len() - common)
+        .chain(to[common..].iter().map(String::as_str))
+        .collect();
+    if parts.is_empty() { ".".to_string() } else { parts.join("/") }
+}
+
+/// Every reference of a manifest keyed by document id, in document order, with their parsed
+/// Bible and publication citations: `{"1102023201": [...], ...}`. Serialize it to get the link
+/// graph without the rest of the manifest.
+pub struct ReferencesByDocument<'a>(pub &'a Manifest);
+
+impl Serialize for ReferencesByDocument<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.documents.iter().map(|document| (document.id, &document.references)))
+    }
+}
+
+/// Writes `ReferencesByDocument` as `<output_dir>/references.json` and returns its path
+#[cfg(feature = "native")]
+pub fn write_references_json<P: AsRef<Path>>(manifest: &Manifest, output_dir: P) -> crate::Result<PathBuf> {
+    let output_dir = output_dir.as_ref();
+    fs::create_dir_all(output_dir)?;
+
+    let path = output_dir.join("references.json");
+    let json = serde_json::to_string_pretty(&ReferencesByDocument(manifest)).map_err(anyhow::Error::from)?;
+    fs::write(&path, json)?;
+    Ok(path)
+}
+
+/// Escapes text for HTML/XML content and double-quoted attributes
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,10 +1,82 @@
-use scraper::{Html, Selector};
-use crate::models::{Reference, ReferenceType, Asset, AssetType};
+use ego_tree::{NodeId, NodeRef};
+use scraper::{CaseSensitivity, ElementRef, Html, Node, Selector};
+use base64::{engine::general_purpose, Engine as _};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use crate::assets::{self as asset_utils, InMemoryAsset};
+use crate::models::{Reference, ReferenceType, Asset, AssetType, BibleCitation, ContentBlock, ContentRegion, Footnote, Heading, List, ListItem, Paragraph, PublicationCitation, StudyQuestion};
+
+/// Everything extracted from the HTML of a single document
+#[derive(Debug, Default)]
+pub struct ParsedHtml {
+    /// HTML with image paths rewritten to `./assets/`
+    pub html: String,
+    /// The input HTML, untouched
+    pub html_raw: String,
+    pub references: Vec<Reference>,
+    pub assets: Vec<Asset>,
+    pub paragraphs: Vec<Paragraph>,
+    pub footnotes: Vec<Footnote>,
+    pub headings: Vec<Heading>,
+    pub questions: Vec<StudyQuestion>,
+    /// Headings, paragraphs, captions, quotes and lists in document order
+    pub content_blocks: Vec<ContentBlock>,
+    /// Whole document as plain text, one block per paragraph separated by blank lines
+    pub plain_text: String,
+    /// Files embedded in the markup (`data:` images and inline `<svg>`), named as listed in `assets`
+    pub embedded_files: Vec<InMemoryAsset>,
+}
+
+/// Elements that start a new block of text in `plain_text`
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "dd", "div", "dl", "dt", "figcaption",
+    "figure", "footer", "h1", "h2", "h3", "h4", "h5", "h6", "header", "li", "main", "nav",
+    "ol", "p", "pre", "section", "table", "td", "th", "tr", "ul",
+];
+
+/// Elements whose content is never text meant for the reader
+const SKIPPED_ELEMENTS: &[&str] = &["script", "style", "template", "noscript", "head"];
+
+/// Which image sources `HtmlParser` rewrites to `./assets/<file>`.
+/// External `http(s)` sources are never touched. `data:` images are written out as files
+/// by every policy except `None`, which keeps them inline and extracts nothing for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RewritePolicy {
+    /// Localizes every image that is not external (`jwpub-media://` and relative paths)
+    #[default]
+    LocalizeAll,
+    /// Only localizes `jwpub-media://` images; any other source is kept as is
+    PreserveExternal,
+    /// Leaves every source untouched
+    None,
+}
+
+impl RewritePolicy {
+    /// Whether an image `src` is rewritten under this policy
+    pub fn rewrites(&self, src: &str) -> bool {
+        let lower = src.to_ascii_lowercase();
+        if lower.starts_with("http://") || lower.starts_with("https://") || lower.starts_with("data:") {
+            return false;
+        }
+
+        match self {
+            RewritePolicy::LocalizeAll => true,
+            RewritePolicy::PreserveExternal => lower.starts_with("jwpub-media://"),
+            RewritePolicy::None => false,
+        }
+    }
+}
 
 pub struct HtmlParser;
 
 impl HtmlParser {
-    pub fn parse(html_content: &str) -> (String, Vec<Reference>, Vec<Asset>, Vec<String>) {
+    /// Parses a document, localizing image sources with `RewritePolicy::LocalizeAll`
+    pub fn parse(html_content: &str) -> ParsedHtml {
+        Self::parse_with_policy(html_content, RewritePolicy::default())
+    }
+
+    /// Parses a document, rewriting image sources as `policy` dictates
+    pub fn parse_with_policy(html_content: &str, policy: RewritePolicy) -> ParsedHtml {
         let document = Html::parse_document(html_content);
         let mut references = Vec::new();
         let mut assets = Vec::new();
@@ -13,26 +85,56 @@ impl HtmlParser {
         // Selectors
         let a_selector = Selector::parse("a").unwrap();
         let img_selector = Selector::parse("img").unwrap();
-        let p_selector = Selector::parse("p").unwrap();
+        let svg_selector = Selector::parse("svg").unwrap();
+        let figcaption_selector = Selector::parse("figcaption").unwrap();
+        let text_block_selector = Selector::parse("h1, h2, h3, h4, h5, h6, p").unwrap();
 
-        // 1. Extract References and Video Links
+        // 1. Extract References and Video Links. Their paragraph is resolved once the
+        // paragraphs are known, from the anchor recorded in `reference_nodes`.
+        let mut reference_nodes = Vec::new();
         for element in document.select(&a_selector) {
             let href = element.value().attr("href").unwrap_or("").to_string();
             let data_video = element.value().attr("data-video").unwrap_or("").to_string();
-            let text = element.text().collect::<Vec<_>>().join(" ").trim().to_string();
+            let data_audio = element.value().attr("data-audio").unwrap_or("").to_string();
+            let text = Self::element_text(&element);
 
             if href.starts_with("bible://") {
                 references.push(Reference {
                     r#type: ReferenceType::Bible,
                     link: href.clone(),
                     text: text.clone(),
+                    bible_citation: BibleCitation::parse(&href),
+                    publication_citation: None,
+                    song_number: None,
+                    paragraph_index: None,
                 });
+                reference_nodes.push(element.id());
             } else if href.starts_with("jwpub://") {
                 references.push(Reference {
                     r#type: ReferenceType::Publication,
                     link: href.clone(),
                     text: text.clone(),
+                    bible_citation: None,
+                    publication_citation: PublicationCitation::parse(&href),
+                    song_number: None,
+                    paragraph_index: None,
                 });
+                reference_nodes.push(element.id());
+            }
+
+            // Song links may also point to the songbook (`jwpub://`) and carry the audio aside
+            if href.starts_with("webpubaudio://") || data_audio.starts_with("webpubaudio://") {
+                let link = if !data_audio.is_empty() { data_audio } else { href.clone() };
+                references.push(Reference {
+                    r#type: ReferenceType::Audio,
+                    song_number: Self::song_number(&link, &text),
+                    link,
+                    text: text.clone(),
+                    bible_citation: None,
+                    publication_citation: None,
+                    paragraph_index: None,
+                });
+                reference_nodes.push(element.id());
             }
 
             if href.starts_with("webpubvid://") || data_video.starts_with("webpubvid://") {
@@ -41,53 +143,746 @@ impl HtmlParser {
                     r#type: ReferenceType::Video,
                     link: link.clone(),
                     text: if text.is_empty() { "Video".to_string() } else { text.clone() },
+                    bible_citation: None,
+                    publication_citation: None,
+                    song_number: None,
+                    paragraph_index: None,
                 });
-                
-                assets.push(Asset {
-                    file_name: link,
-                    alt_text: if text.is_empty() { "Video".to_string() } else { text },
-                    r#type: AssetType::Video,
-                });
+                reference_nodes.push(element.id());
             }
         }
 
-        // 2. Extract Images & Rewrite Paths (Simulation)
-        // Note: Real rewriting would involve modifying the DOM tree. 
-        // scraper is mostly for parsing/extracting. For rewriting attributes effectively 
-        // while keeping the structure, we might need to serialize differently or just 
-        // return the extracted assets and let the frontend map them.
-        // HOWEVER, for this output, we will return the raw HTML and the assets list.
-        // The frontend can replace `src="path"` with the local asset URL easily or we can do string replacement.
-        // For simplicity and speed in Rust, string replacement on the final HTML is often faster than DOM manipulation for this specific task.
-        
-        let mut modified_html = html_content.to_string();
-
+        // 2. Extract Images & Rewrite Paths. `data:` images are decoded into files named
+        // after their content, so the same picture embedded twice is written once.
+        let mut embedded_files = Vec::new();
+        let mut data_uri_files = HashMap::new();
         for element in document.select(&img_selector) {
-            let src = element.value().attr("src").unwrap_or("").to_string();
-            let alt = element.value().attr("alt").unwrap_or("").to_string();
+            let src = element.value().attr("src").unwrap_or("");
+            let alt = element.value().attr("alt").unwrap_or("").trim().to_string();
+            let caption = Self::figure_caption(&element, &figcaption_selector);
+
+            let (file_name, mime) = if src.to_ascii_lowercase().starts_with("data:") {
+                // Undecodable data URIs are left in the HTML and not listed as assets, and so are
+                // all of them under `RewritePolicy::None`, where the file would go unreferenced
+                if policy == RewritePolicy::None { continue; }
+                let Some((bytes, extension)) = Self::decode_data_uri(src) else { continue };
+                let file_name = Self::embedded_file_name(&bytes, extension);
+                data_uri_files.insert(src.to_string(), file_name.clone());
+                embedded_files.push((file_name.clone(), bytes));
+                let mime = asset_utils::mime_type_for(&file_name).to_string();
+                (file_name, Some(mime))
+            } else {
+                (Self::asset_file_name(src), None)
+            };
+
+            assets.push(Asset {
+                file_name,
+                // Images often leave `alt` empty and describe themselves in the caption
+                alt_text: if alt.is_empty() { caption.clone().unwrap_or_default() } else { alt },
+                caption,
+                r#type: AssetType::Image,
+                width: None,
+                height: None,
+                mime,
+            });
+        }
+
+        // Inline diagrams stay in the HTML, but are also saved as standalone `.svg` files
+        for element in document.select(&svg_selector) {
+            let nested = element.ancestors().filter_map(ElementRef::wrap).any(|ancestor| ancestor.value().name() == "svg");
+            if nested { continue; }
 
-            let file_name = src.replace("jwpub-media://", "");
-            let file_name = file_name.split('/').last().unwrap_or(&file_name).to_string();
+            let bytes = Self::standalone_svg(&element).into_bytes();
+            let file_name = Self::embedded_file_name(&bytes, "svg");
+            embedded_files.push((file_name.clone(), bytes));
 
+            let caption = Self::figure_caption(&element, &figcaption_selector);
+            let label = element.value().attr("aria-label").unwrap_or("").trim().to_string();
+            let mime = asset_utils::mime_type_for(&file_name).to_string();
             assets.push(Asset {
-                file_name: file_name.clone(),
-                alt_text: alt,
+                file_name,
+                alt_text: if label.is_empty() { caption.clone().unwrap_or_default() } else { label },
+                caption,
                 r#type: AssetType::Image,
+                width: None,
+                height: None,
+                mime: Some(mime),
+            });
+        }
+
+        // The same image may be referenced several times; keep the first occurrence
+        let mut seen_assets = HashSet::new();
+        assets.retain(|asset| seen_assets.insert(asset.file_name.clone()));
+        let mut seen_files = HashSet::new();
+        embedded_files.retain(|(file_name, _)| seen_files.insert(file_name.clone()));
+
+        let modified_html = Self::rewrite_image_sources(html_content, |src| match data_uri_files.get(src) {
+            Some(file_name) => Some(format!("./assets/{}", file_name)),
+            None => policy.rewrites(src).then(|| format!("./assets/{}", Self::asset_file_name(src))),
+        });
+
+        // 3. Extract Paragraphs & Headings (single pass to keep their relative order)
+        let mut headings = Vec::new();
+        let mut paragraph_nodes = HashMap::new();
+        for element in document.select(&text_block_selector) {
+            let text = Self::element_text(&element);
+            if text.is_empty() { continue; }
+
+            let name = element.value().name();
+            if name == "p" {
+                paragraph_nodes.insert(element.id(), paragraphs.len());
+                paragraphs.push(Paragraph {
+                    id: element.value().id().map(|id| id.to_string()),
+                    data_pid: element.value().attr("data-pid").map(|pid| pid.to_string()),
+                    text,
+                });
+            } else {
+                headings.push(Heading {
+                    level: name[1..].parse().unwrap_or(1),
+                    text,
+                    id: element.value().id().map(|id| id.to_string()),
+                    paragraph_offset: paragraphs.len(),
+                });
+            }
+        }
+
+        for (reference, node) in references.iter_mut().zip(&reference_nodes) {
+            reference.paragraph_index = document.tree.get(*node).and_then(|anchor| {
+                anchor.ancestors().find_map(|ancestor| paragraph_nodes.get(&ancestor.id()).copied())
+            });
+        }
+
+        // 4. Extract Footnotes
+        let footnotes = Self::extract_footnotes(&document, &paragraph_nodes);
+
+        // 5. Extract Study Questions
+        let questions = Self::extract_questions(&document);
+
+        // 6. Content Blocks in reading order
+        let mut content_blocks = Vec::new();
+        Self::collect_content_blocks(*document.root_element(), ContentRegion::Body, &mut content_blocks);
+
+        // 7. Render Plain Text
+        let mut blocks = Vec::new();
+        let mut current = String::new();
+        Self::collect_text(*document.root_element(), &mut blocks, &mut current);
+        Self::flush_block(&mut current, &mut blocks);
+
+        ParsedHtml {
+            html: modified_html,
+            html_raw: html_content.to_string(),
+            references,
+            assets,
+            paragraphs,
+            footnotes,
+            headings,
+            questions,
+            content_blocks,
+            plain_text: blocks.join("\n\n"),
+            embedded_files,
+        }
+    }
+
+    /// Text of the `figcaption` of the `figure` enclosing `element`, if any
+    fn figure_caption(element: &ElementRef, figcaption_selector: &Selector) -> Option<String> {
+        element
+            .ancestors()
+            .filter_map(ElementRef::wrap)
+            .find(|ancestor| ancestor.value().name() == "figure")
+            .and_then(|figure| figure.select(figcaption_selector).next())
+            .map(|figcaption| Self::element_text(&figcaption))
+            .filter(|caption| !caption.is_empty())
+    }
+
+    /// Decodes a base64 `data:image/...` URI into its bytes and a file extension.
+    /// The extension comes from the declared MIME type, or from the bytes when it is unknown.
+    fn decode_data_uri(src: &str) -> Option<(Vec<u8>, &'static str)> {
+        let (header, payload) = src.get(5..)?.split_once(',')?;
+        let mut parts = header.split(';');
+        let mime = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+        if !parts.any(|part| part.trim().eq_ignore_ascii_case("base64")) {
+            return None;
+        }
+
+        let payload: String = payload.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+        let bytes = general_purpose::STANDARD.decode(payload).ok()?;
+        let extension = match mime.as_str() {
+            "image/jpeg" | "image/jpg" => "jpg",
+            "image/png" => "png",
+            "image/gif" => "gif",
+            "image/webp" => "webp",
+            "image/svg+xml" => "svg",
+            _ => asset_utils::sniff_image_extension(&bytes)?,
+        };
+        Some((bytes, extension))
+    }
+
+    /// `embedded_<hash>.<extension>`, stable for the same content across documents
+    fn embedded_file_name(bytes: &[u8], extension: &str) -> String {
+        let hash = hex::encode(Sha256::digest(bytes));
+        format!("embedded_{}.{}", &hash[..16], extension)
+    }
+
+    /// Serializes an inline `<svg>` as a file of its own, adding the namespace HTML lets it omit
+    fn standalone_svg(element: &ElementRef) -> String {
+        let markup = element.html();
+        if element.value().attr("xmlns").is_some() {
+            return markup;
+        }
+        markup.replacen("<svg", "<svg xmlns=\"http://www.w3.org/2000/svg\"", 1)
+    }
+
+    /// Walks the tree emitting a block per heading, paragraph, caption, quote and list. Their
+    /// content is taken whole, so e.g. the paragraphs inside a `blockquote` are part of its `Quote`.
+    /// Each block is tagged with the region of its innermost classified container.
+    fn collect_content_blocks(node: NodeRef<Node>, region: ContentRegion, blocks: &mut Vec<ContentBlock>) {
+        for child in node.children() {
+            let element = match ElementRef::wrap(child) {
+                Some(element) => element,
+                None => continue,
+            };
+            let name = element.value().name();
+            if SKIPPED_ELEMENTS.contains(&name) { continue; }
+            let region = Self::content_region(&element).unwrap_or(region);
+
+            if matches!(name, "ul" | "ol") {
+                let list = Self::collect_list(&element);
+                if !list.items.is_empty() {
+                    blocks.push(ContentBlock::List { ordered: list.ordered, items: list.items, region });
+                }
+                continue;
+            }
+
+            if !matches!(name, "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "p" | "figcaption" | "blockquote") {
+                Self::collect_content_blocks(child, region, blocks);
+                continue;
+            }
+
+            let text = Self::element_text(&element);
+            if text.is_empty() { continue; }
+
+            blocks.push(match name {
+                "p" => ContentBlock::Paragraph { text, region },
+                "figcaption" => ContentBlock::Caption { text, region },
+                "blockquote" => ContentBlock::Quote { text, region },
+                _ => ContentBlock::Heading { level: name[1..].parse().unwrap_or(1), text, region },
+            });
+        }
+    }
+
+    /// Region an element starts, going by the class names of JW documents; `None` when it
+    /// stays in the region of its parent
+    fn content_region(element: &ElementRef) -> Option<ContentRegion> {
+        let value = element.value();
+        if value.name() == "header" {
+            return Some(ContentRegion::Header);
+        }
+        if value.name() == "aside" {
+            return Some(ContentRegion::Box);
+        }
+
+        let mut region = None;
+        for class in value.classes().map(|class| class.to_ascii_lowercase()) {
+            if class == "blockteach" || class.contains("review") {
+                return Some(ContentRegion::Review);
+            }
+            if class == "footnote" || class == "footnotes" {
+                return Some(ContentRegion::Footnote);
+            }
+            if class == "boxcontent" || class == "boxsupplement" {
+                region = Some(ContentRegion::Box);
+            }
+        }
+        region
+    }
+
+    /// Reads the `li` children of a `ul`/`ol`, keeping nested lists under their item
+    fn collect_list(element: &ElementRef) -> List {
+        let items = element
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|child| child.value().name() == "li")
+            .map(|item| {
+                let mut text = String::new();
+                let mut lists = Vec::new();
+                Self::collect_list_item(*item, &mut text, &mut lists);
+                ListItem { text: normalize_text(&text), lists }
+            })
+            .filter(|item| !item.text.is_empty() || !item.lists.is_empty())
+            .collect();
+        List { ordered: element.value().name() == "ol", items }
+    }
+
+    /// Accumulates the text of an `li`, setting nested lists aside instead of flattening them
+    fn collect_list_item(node: NodeRef<Node>, text: &mut String, lists: &mut Vec<List>) {
+        for child in node.children() {
+            match child.value() {
+                Node::Text(value) => text.push_str(value),
+                Node::Element(element) => {
+                    let name = element.name();
+                    if SKIPPED_ELEMENTS.contains(&name) { continue; }
+                    if matches!(name, "ul" | "ol") {
+                        if let Some(nested) = ElementRef::wrap(child) {
+                            lists.push(Self::collect_list(&nested));
+                        }
+                        continue;
+                    }
+
+                    let is_block = name == "br" || BLOCK_ELEMENTS.contains(&name);
+                    if is_block { text.push(' '); }
+                    Self::collect_list_item(child, text, lists);
+                    if is_block { text.push(' '); }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Walks the tree accumulating text, closing a block at every block-level element boundary
+    fn collect_text(node: NodeRef<Node>, blocks: &mut Vec<String>, current: &mut String) {
+        Self::collect_text_excluding(node, &HashSet::new(), blocks, current);
+    }
+
+    /// `collect_text` leaving out the subtrees rooted at `excluded`
+    fn collect_text_excluding(node: NodeRef<Node>, excluded: &HashSet<NodeId>, blocks: &mut Vec<String>, current: &mut String) {
+        for child in node.children() {
+            if excluded.contains(&child.id()) { continue; }
+            match child.value() {
+                Node::Text(text) => current.push_str(text),
+                Node::Element(element) => {
+                    let name = element.name();
+                    if SKIPPED_ELEMENTS.contains(&name) { continue; }
+                    if name == "br" {
+                        current.push(' ');
+                        continue;
+                    }
+
+                    let is_block = BLOCK_ELEMENTS.contains(&name);
+                    if is_block { Self::flush_block(current, blocks); }
+                    Self::collect_text_excluding(child, excluded, blocks, current);
+                    if is_block { Self::flush_block(current, blocks); }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Pushes the accumulated text as a block, cleaned up by `normalize_text`
+    fn flush_block(current: &mut String, blocks: &mut Vec<String>) {
+        let text = normalize_text(current);
+        if !text.is_empty() {
+            blocks.push(text);
+        }
+        current.clear();
+    }
+
+    /// Collects footnote bodies (`[data-fnid]` elements) and the paragraph holding their marker.
+    ///
+    /// Markers are `a[data-fnid]` / `a.fn` anchors. When a marker has no matching `data-fnid`
+    /// body, its `href="#..."` target is used instead.
+    fn extract_footnotes(document: &Html, paragraph_nodes: &HashMap<NodeId, usize>) -> Vec<Footnote> {
+        let marker_selector = Selector::parse("a[data-fnid], a.fn").unwrap();
+        let body_selector = Selector::parse("[data-fnid]:not(a)").unwrap();
+        let id_selector = Selector::parse("[id]").unwrap();
+
+        let mut marker_paragraphs = HashMap::new();
+        let mut marker_targets = Vec::new();
+        for marker in document.select(&marker_selector) {
+            let target = marker.value().attr("href")
+                .and_then(|href| href.strip_prefix('#'))
+                .map(|target| target.to_string());
+            let id = match marker.value().attr("data-fnid").map(|id| id.to_string()).or_else(|| target.clone()) {
+                Some(id) => id,
+                None => continue,
+            };
+
+            let paragraph_index = marker.ancestors().find_map(|node| paragraph_nodes.get(&node.id()).copied());
+            if let Some(paragraph_index) = paragraph_index {
+                marker_paragraphs.entry(id.clone()).or_insert(paragraph_index);
+            }
+            if let Some(target) = target {
+                marker_targets.push((id, target));
+            }
+        }
+
+        // Bodies first, so a footnote nested in another can be left out of the outer text
+        let mut bodies = Vec::new();
+        let mut seen = HashSet::new();
+        for body in document.select(&body_selector) {
+            let id = body.value().attr("data-fnid").unwrap_or_default().to_string();
+            if seen.insert(id.clone()) {
+                bodies.push((id, body));
+            }
+        }
+        for (id, target) in marker_targets {
+            if seen.contains(&id) { continue; }
+
+            let body = document.select(&id_selector).find(|element| element.value().id() == Some(target.as_str()));
+            if let Some(body) = body {
+                seen.insert(id.clone());
+                bodies.push((id, body));
+            }
+        }
+
+        let body_nodes: HashSet<NodeId> = bodies.iter().map(|(_, body)| body.id()).collect();
+        let bible_selector = Selector::parse("a[href^=\"bible://\"]").unwrap();
+        bodies
+            .into_iter()
+            .map(|(id, body)| {
+                let nested: HashSet<NodeId> = body
+                    .descendants()
+                    .skip(1)
+                    .map(|node| node.id())
+                    .filter(|node| body_nodes.contains(node))
+                    .collect();
+
+                let mut blocks = Vec::new();
+                let mut current = String::new();
+                Self::collect_text_excluding(*body, &nested, &mut blocks, &mut current);
+                Self::flush_block(&mut current, &mut blocks);
+
+                // Links of a nested footnote belong to that one
+                let bible_citations = body
+                    .select(&bible_selector)
+                    .filter(|link| {
+                        link.ancestors().find(|node| body_nodes.contains(&node.id())).map(|node| node.id()) == Some(body.id())
+                    })
+                    .filter_map(|link| BibleCitation::parse(link.value().attr("href")?))
+                    .collect();
+
+                Footnote {
+                    paragraph_index: marker_paragraphs.get(&id).copied(),
+                    text: blocks.join(" "),
+                    bible_citations,
+                    id,
+                }
+            })
+            .collect()
+    }
+
+    /// Collects study questions (`.qu` elements, outermost only) and the paragraph numbers
+    /// they open with, e.g. `"1, 2. What happened?"` or `"3-5. Why...?"`
+    fn extract_questions(document: &Html) -> Vec<StudyQuestion> {
+        let question_selector = Selector::parse(".qu").unwrap();
+
+        let mut questions = Vec::new();
+        for element in document.select(&question_selector) {
+            let nested = element
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .any(|ancestor| ancestor.value().has_class("qu", CaseSensitivity::AsciiCaseInsensitive));
+            if nested { continue; }
+
+            let text = Self::element_text(&element);
+            if text.is_empty() { continue; }
+
+            let (paragraph_refs, text) = Self::split_question_refs(&text);
+            questions.push(StudyQuestion { text, paragraph_refs });
+        }
+
+        questions
+    }
+
+    /// Splits the leading `1, 2.` / `3-5.` paragraph numbers off a question
+    fn split_question_refs(text: &str) -> (Vec<String>, String) {
+        let prefix_len = text
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, ',' | '-' | '–' | ' ')))
+            .unwrap_or(text.len());
+        let (prefix, rest) = text.split_at(prefix_len);
+        let question = match rest.strip_prefix('.') {
+            Some(question) if prefix.chars().any(|c| c.is_ascii_digit()) => question,
+            _ => return (Vec::new(), text.to_string()),
+        };
+
+        let mut refs = Vec::new();
+        for part in prefix.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let range = part.split_once(['-', '–']).and_then(|(start, end)| {
+                Some((start.trim().parse::<u32>().ok()?, end.trim().parse::<u32>().ok()?))
             });
+            match range {
+                Some((start, end)) if start <= end => refs.extend((start..=end).map(|n| n.to_string())),
+                _ => refs.push(part.to_string()),
+            }
+        }
+
+        (refs, question.trim().to_string())
+    }
+
+    /// Text content of an element as `normalize_text` leaves it. Block-level children are
+    /// separated by a space; inline markup (`<em>`, links) is not, so no stray spaces appear
+    /// before punctuation.
+    fn element_text(element: &ElementRef) -> String {
+        let mut blocks = Vec::new();
+        let mut current = String::new();
+        Self::collect_text(**element, &mut blocks, &mut current);
+        Self::flush_block(&mut current, &mut blocks);
+        blocks.join(" ")
+    }
 
-            // Basic string replacement for paths (Naive but effective for standard JWPUB HTML)
-            // We replace the original src with a relative path
-            modified_html = modified_html.replace(&src, &format!("./assets/{}", file_name));
+    /// Song number of an audio link: the last numeric segment of the link
+    /// (`webpubaudio://sjjm/E/123`, `...?track=123`), or else the number in the link text
+    /// ("Song 123")
+    fn song_number(link: &str, text: &str) -> Option<u32> {
+        let from_link = link
+            .trim_start_matches("webpubaudio://")
+            .rsplit(['/', '?', '&', '=', ':'])
+            .find_map(|segment| segment.parse().ok());
+
+        from_link.or_else(|| {
+            text.split(|c: char| !c.is_ascii_digit())
+                .find(|digits| !digits.is_empty())
+                .and_then(|digits| digits.parse().ok())
+        })
+    }
+
+    /// Maps an image `src` (e.g. `jwpub-media://folder/image.jpg`) to the bare asset file name
+    fn asset_file_name(src: &str) -> String {
+        let file_name = src.replace("jwpub-media://", "");
+        file_name.split('/').next_back().unwrap_or(&file_name).to_string()
+    }
+
+    /// Rewrites the `src` attribute of every `img` element through `resolve`, leaving the
+    /// rest of the markup untouched. Returning `None` keeps the original value. When no source
+    /// changes the input is returned as is, without going through the DOM.
+    ///
+    /// Documents are usually fragments (`<header>...</header><div class="bodyTxt">...`), so they
+    /// are re-serialized as fragments unless the input carries its own `<html>` element.
+    pub(crate) fn rewrite_image_sources<F>(html_content: &str, resolve: F) -> String
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let is_full_document = html_content.to_ascii_lowercase().contains("<html");
+        let mut dom = if is_full_document {
+            Html::parse_document(html_content)
+        } else {
+            Html::parse_fragment(html_content)
+        };
+
+        let img_selector = Selector::parse("img[src]").unwrap();
+        let targets: Vec<_> = dom
+            .select(&img_selector)
+            .filter_map(|element| {
+                let src = element.value().attr("src")?;
+                resolve(src).filter(|new_src| new_src != src).map(|new_src| (element.id(), new_src))
+            })
+            .collect();
+        if targets.is_empty() {
+            return html_content.to_string();
         }
 
-        // 3. Extract Paragraphs
-        for element in document.select(&p_selector) {
-            let text = element.text().collect::<Vec<_>>().join(" ").trim().to_string();
-            if !text.is_empty() {
-                paragraphs.push(text);
+        for (id, new_src) in targets {
+            if let Some(mut node) = dom.tree.get_mut(id) {
+                if let Node::Element(element) = node.value() {
+                    for (name, value) in element.attrs.iter_mut() {
+                        if &*name.local == "src" {
+                            *value = new_src.as_str().into();
+                        }
+                    }
+                }
             }
         }
 
-        (modified_html, references, assets, paragraphs)
+        if is_full_document {
+            dom.html()
+        } else {
+            dom.root_element().inner_html()
+        }
+    }
+}
+
+/// Cleans up text taken from the markup: collapses runs of whitespace, non-breaking spaces
+/// included, into single spaces and trims the ends. Entities are already decoded by the HTML
+/// parser, so anything that still looks like one is literal text and is kept as is.
+pub fn normalize_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlapping_image_sources_are_rewritten_independently() {
+        let html = r#"<p><img src="jwpub-media://a.jpg" data-full="jwpub-media://a.jpg"><img src="jwpub-media://photos/ba.jpg"><a href="jwpub-media://a.jpg">a.jpg</a></p>"#;
+        let parsed = HtmlParser::parse(html);
+
+        assert!(parsed.html.contains(r#"<img src="./assets/a.jpg" data-full="jwpub-media://a.jpg">"#));
+        assert!(parsed.html.contains(r#"<img src="./assets/ba.jpg">"#));
+        assert!(parsed.html.contains(r#"<a href="jwpub-media://a.jpg">a.jpg</a>"#));
+        let names: Vec<_> = parsed.assets.iter().map(|asset| asset.file_name.as_str()).collect();
+        assert_eq!(names, ["a.jpg", "ba.jpg"]);
+    }
+
+    const POLICY_HTML: &str = r#"<p><img src="jwpub-media://a.jpg"><img src="images/b.png"><img src="https://cdn.example.org/c.jpg"></p>"#;
+
+    fn image_sources(policy: RewritePolicy) -> Vec<String> {
+        let parsed = HtmlParser::parse_with_policy(POLICY_HTML, policy);
+        let fragment = Html::parse_fragment(&parsed.html);
+        let img_selector = Selector::parse("img").unwrap();
+        fragment.select(&img_selector).filter_map(|img| img.value().attr("src")).map(String::from).collect()
+    }
+
+    #[test]
+    fn localize_all_rewrites_every_local_source() {
+        assert_eq!(
+            image_sources(RewritePolicy::LocalizeAll),
+            ["./assets/a.jpg", "./assets/b.png", "https://cdn.example.org/c.jpg"]
+        );
+    }
+
+    #[test]
+    fn preserve_external_only_rewrites_jwpub_media() {
+        assert_eq!(
+            image_sources(RewritePolicy::PreserveExternal),
+            ["./assets/a.jpg", "images/b.png", "https://cdn.example.org/c.jpg"]
+        );
+    }
+
+    #[test]
+    fn none_leaves_every_source_untouched() {
+        assert_eq!(
+            image_sources(RewritePolicy::None),
+            ["jwpub-media://a.jpg", "images/b.png", "https://cdn.example.org/c.jpg"]
+        );
+    }
+
+    #[test]
+    fn figure_caption_fills_empty_alt_text() {
+        let html = r#"<figure><img src="jwpub-media://a.jpg" alt=""><figcaption>A <em>family</em> studying</figcaption></figure><figure><img src="jwpub-media://b.jpg" alt="Open Bible"><figcaption>Read daily</figcaption></figure><img src="jwpub-media://c.jpg" alt="">"#;
+        let parsed = HtmlParser::parse(html);
+
+        let [a, b, c] = &parsed.assets[..] else { panic!("expected three assets, got {:?}", parsed.assets) };
+        assert_eq!(a.caption.as_deref(), Some("A family studying"));
+        assert_eq!(a.alt_text, "A family studying");
+        assert_eq!(b.caption.as_deref(), Some("Read daily"));
+        assert_eq!(b.alt_text, "Open Bible");
+        assert_eq!(c.caption, None);
+        assert_eq!(c.alt_text, "");
+    }
+
+    /// A 1x1 PNG
+    const PNG_DATA_URI: &str = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNk+M9QDwADhgGAWjR9awAAAABJRU5ErkJggg==";
+
+    #[test]
+    fn data_uri_images_become_asset_files() {
+        let html = format!(r#"<p><img src="{uri}" alt="Dot"><img src="{uri}"></p>"#, uri = PNG_DATA_URI);
+        let parsed = HtmlParser::parse(&html);
+
+        let [(file_name, bytes)] = &parsed.embedded_files[..] else { panic!("expected one file, got {:?}", parsed.embedded_files.len()) };
+        assert!(file_name.starts_with("embedded_") && file_name.ends_with(".png"));
+        assert!(bytes.starts_with(b"\x89PNG"));
+        assert_eq!(parsed.assets.len(), 1);
+        assert_eq!(parsed.assets[0].file_name, *file_name);
+        assert_eq!(parsed.assets[0].mime.as_deref(), Some("image/png"));
+        assert_eq!(parsed.html.matches(&format!("src=\"./assets/{}\"", file_name)).count(), 2);
+        assert!(!parsed.html.contains("data:"));
+    }
+
+    #[test]
+    fn data_uri_images_stay_inline_without_rewriting() {
+        let html = format!(r#"<p><img src="{}"></p>"#, PNG_DATA_URI);
+        let parsed = HtmlParser::parse_with_policy(&html, RewritePolicy::None);
+
+        assert!(parsed.embedded_files.is_empty());
+        assert!(parsed.assets.is_empty());
+        assert!(parsed.html.contains(PNG_DATA_URI));
+    }
+
+    #[test]
+    fn inline_svg_becomes_a_standalone_file() {
+        let html = r#"<figure><svg viewBox="0 0 10 10"><circle cx="5" cy="5" r="4"></circle></svg><figcaption>Diagram</figcaption></figure>"#;
+        let parsed = HtmlParser::parse(html);
+
+        let [(file_name, bytes)] = &parsed.embedded_files[..] else { panic!("expected one file, got {:?}", parsed.embedded_files.len()) };
+        assert!(file_name.starts_with("embedded_") && file_name.ends_with(".svg"));
+        let svg = String::from_utf8(bytes.clone()).unwrap();
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg""#));
+        assert!(svg.contains("<circle"));
+        assert_eq!(parsed.assets[0].file_name, *file_name);
+        assert_eq!(parsed.assets[0].alt_text, "Diagram");
+        assert_eq!(parsed.assets[0].mime.as_deref(), Some("image/svg+xml"));
+        assert_eq!(asset_utils::mime_type_for("Diagram.SVG"), "image/svg+xml");
+        assert!(parsed.html.contains("<svg"));
+    }
+
+    #[test]
+    fn nested_ordered_lists_keep_their_structure() {
+        let html = "<ol><li>Opening song</li><li>Treasures<ol><li>Talk</li><li>Spiritual gems</li></ol></li></ol>";
+        let parsed = HtmlParser::parse(html);
+
+        let [ContentBlock::List { ordered: true, items, .. }] = &parsed.content_blocks[..] else {
+            panic!("expected one ordered list, got {:?}", parsed.content_blocks)
+        };
+        let texts: Vec<_> = items.iter().map(|item| item.text.as_str()).collect();
+        assert_eq!(texts, ["Opening song", "Treasures"]);
+        assert!(items[0].lists.is_empty());
+
+        let [nested] = &items[1].lists[..] else { panic!("expected one nested list") };
+        assert!(nested.ordered);
+        let nested_texts: Vec<_> = nested.items.iter().map(|item| item.text.as_str()).collect();
+        assert_eq!(nested_texts, ["Talk", "Spiritual gems"]);
+    }
+
+    #[test]
+    fn audio_links_become_song_references() {
+        let html = r#"<p>Song <a href="webpubaudio://sjjm/E/123">123</a> and <a href="jwpub://p/E:1102016800/0" data-audio="webpubaudio://sjjm/E?track=45">Song 45</a></p><p><a href="webpubaudio://sjjm/E">Song 7</a></p>"#;
+        let parsed = HtmlParser::parse(html);
+
+        let audio: Vec<_> = parsed.references.iter().filter(|reference| reference.r#type == ReferenceType::Audio).collect();
+        let songs: Vec<_> = audio.iter().map(|reference| (reference.link.as_str(), reference.song_number, reference.paragraph_index)).collect();
+        assert_eq!(
+            songs,
+            [
+                ("webpubaudio://sjjm/E/123", Some(123), Some(0)),
+                ("webpubaudio://sjjm/E?track=45", Some(45), Some(0)),
+                ("webpubaudio://sjjm/E", Some(7), Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn videos_are_references_only() {
+        let html = r#"<p><a href="webpubvid://pub-mwbv_1_VIDEO">Watch</a><img src="jwpub-media://photo.jpg"></p>"#;
+        let parsed = HtmlParser::parse(html);
+
+        let videos: Vec<_> = parsed.references.iter().filter(|reference| reference.r#type == ReferenceType::Video).map(|reference| reference.link.as_str()).collect();
+        assert_eq!(videos, ["webpubvid://pub-mwbv_1_VIDEO"]);
+        assert!(parsed.assets.iter().all(|asset| asset.r#type == AssetType::Image));
+    }
+
+    #[test]
+    fn normalize_text_keeps_literal_entities() {
+        let parsed = HtmlParser::parse("<p>AT&amp;amp;T writes &amp;lt;b&amp;gt;\u{a0} and&nbsp;&nbsp;more\n  text</p>");
+        assert_eq!(parsed.paragraphs[0].text, "AT&amp;T writes &lt;b&gt; and more text");
+        assert_eq!(normalize_text("  a \t\u{a0}b\n"), "a b");
+    }
+
+    #[test]
+    fn markup_without_rewritten_images_is_kept_verbatim() {
+        let html = "<P CLASS=x>Text<br/>with <b>markup</P><img src=\"https://cdn.example.org/a.jpg\">";
+        assert_eq!(HtmlParser::parse(html).html, html);
+        assert_eq!(HtmlParser::rewrite_image_sources(html, |src| Some(src.to_string())), html);
+    }
+
+    #[test]
+    fn nested_footnotes_keep_their_own_text() {
+        let html = r##"<p id="p1">Text<a class="fn" data-fnid="1" href="#fn1">*</a></p><div class="footnotes"><div data-fnid="1">Outer note<div data-fnid="2">Inner note</div></div></div>"##;
+        let parsed = HtmlParser::parse(html);
+
+        let notes: Vec<_> = parsed.footnotes.iter().map(|note| (note.id.as_str(), note.text.as_str(), note.paragraph_index)).collect();
+        assert_eq!(notes, [("1", "Outer note", Some(0)), ("2", "Inner note", None)]);
+    }
+
+    #[test]
+    fn footnote_bible_links_are_attached_to_their_footnote() {
+        let html = r##"<p id="p1">Text<a class="fn" data-fnid="1" href="#fn1">*</a> <a href="bible://NWTR/E/1001001">Gen 1:1</a></p><div data-fnid="1">See <a href="bible://NWTR/E/43003016">John 3:16</a><div data-fnid="2">Also <a href="bible://NWTR/E/19023001">Ps 23:1</a></div></div>"##;
+        let parsed = HtmlParser::parse(html);
+
+        let verses: Vec<Vec<(u16, u16, u16)>> = parsed
+            .footnotes
+            .iter()
+            .map(|note| note.bible_citations.iter().map(|c| (c.book, c.chapter_start, c.verse_start)).collect())
+            .collect();
+        assert_eq!(verses, [vec![(43, 3, 16)], vec![(19, 23, 1)]]);
     }
 }
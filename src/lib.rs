@@ -1,118 +1,1220 @@
+pub mod assets;
+#[cfg(feature = "discovery")]
 pub mod discovery;
 pub mod crypto;
 pub mod db;
+pub mod error;
+pub mod export;
 pub mod html;
 pub mod models;
 
-use anyhow::{anyhow, Result};
-use std::fs::{self, File};
-use std::io::Read;
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
 use zip::ZipArchive;
 
-use crate::crypto::CryptoService;
+#[cfg(feature = "native")]
+use std::fs::{self, File};
+#[cfg(feature = "native")]
+use std::path::PathBuf;
+
+#[cfg(feature = "native")]
+use crate::assets::FsAssetSink;
+#[cfg(feature = "native")]
+use crate::models::{DatabaseDump, DocumentSummary, ValidationReport};
+use crate::assets::{AssetNaming, AssetSink, ContentAddressedSink, ImageInfo, InMemoryAsset, MemoryAssetSink};
+use crate::crypto::{CryptoService, EncryptionMode};
 use crate::db::DatabaseService;
-use crate::html::HtmlParser;
-use crate::models::{Manifest, Document};
+use crate::html::{HtmlParser, RewritePolicy};
+use crate::models::{ArchiveManifest, ArchiveManifestPublication, AssetType, DbPublication, Manifest, Document, DocumentError, DocumentRow, ExtractedAsset, IssueInfo, ParseOutput, ParseProgress, ParseStage, ParseStats, StageTimings, TextDirection};
+
+pub use crate::error::{JwpubError, KeyError};
+
+/// Result type of the public parse entry points
+pub type Result<T> = std::result::Result<T, JwpubError>;
+/// Documents tried by the early key check before giving up on the derived key
+const KEY_CHECK_SAMPLES: usize = 3;
+
+/// Callback receiving progress events while a publication is parsed
+pub type ProgressCallback = Arc<dyn Fn(ParseProgress) + Send + Sync>;
+
+/// Options controlling how a publication is extracted
+#[derive(Clone)]
+pub struct ParseOptions {
+    /// Forces the document class to extract. When `None` it is looked up by publication type
+    /// and symbol, falling back to every class for publications the crate does not know.
+    pub class_id: Option<i32>,
+    /// Extracts every document class found in the database, ignoring `class_id`
+    pub all_classes: bool,
+    /// Records documents that fail to decrypt in `Manifest::errors` and keeps going
+    /// instead of aborting the whole parse
+    pub skip_failed: bool,
+    /// Copies the image files out of the archive. When `false` the documents still list their
+    /// `assets`, but nothing is written. Defaults to `true`.
+    pub extract_assets: bool,
+    /// Which image sources are rewritten to `./assets/`
+    pub rewrite_policy: RewritePolicy,
+    /// File names of the extracted assets. With `ContentAddressed` the documents, the cover
+    /// and the HTML point to the hashed paths.
+    pub asset_naming: AssetNaming,
+    /// Documents to skip, in `DocumentId` order within each class, before any is decrypted
+    pub offset: usize,
+    /// Maximum number of documents to process after `offset`; `None` processes them all
+    pub limit: Option<usize>,
+    /// Whether the database content is encrypted. `Auto` also reads plaintext databases.
+    pub encryption: EncryptionMode,
+    /// Called as each stage advances. `None` skips reporting entirely.
+    pub on_progress: Option<ProgressCallback>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            class_id: None,
+            all_classes: false,
+            skip_failed: false,
+            extract_assets: true,
+            rewrite_policy: RewritePolicy::default(),
+            asset_naming: AssetNaming::default(),
+            offset: 0,
+            limit: None,
+            encryption: EncryptionMode::default(),
+            on_progress: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Starts from the defaults, e.g. `ParseOptions::builder().class_id(106).skip_assets(true).build()`
+    pub fn builder() -> ParseOptionsBuilder {
+        ParseOptionsBuilder::default()
+    }
+
+    fn report(&self, stage: ParseStage, current: usize, total: usize) {
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(ParseProgress { stage, current, total });
+        }
+    }
+}
+
+/// Builder for `ParseOptions`; unset fields keep their default
+#[derive(Clone, Default)]
+pub struct ParseOptionsBuilder {
+    options: ParseOptions,
+}
+
+impl ParseOptionsBuilder {
+    pub fn class_id(mut self, class_id: i32) -> Self {
+        self.options.class_id = Some(class_id);
+        self
+    }
+
+    pub fn all_classes(mut self, all_classes: bool) -> Self {
+        self.options.all_classes = all_classes;
+        self
+    }
+
+    pub fn skip_failed(mut self, skip_failed: bool) -> Self {
+        self.options.skip_failed = skip_failed;
+        self
+    }
+
+    /// Opposite of `ParseOptions::extract_assets`
+    pub fn skip_assets(mut self, skip_assets: bool) -> Self {
+        self.options.extract_assets = !skip_assets;
+        self
+    }
+
+    pub fn rewrite_policy(mut self, rewrite_policy: RewritePolicy) -> Self {
+        self.options.rewrite_policy = rewrite_policy;
+        self
+    }
+
+    pub fn asset_naming(mut self, asset_naming: AssetNaming) -> Self {
+        self.options.asset_naming = asset_naming;
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.options.offset = offset;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.options.limit = Some(limit);
+        self
+    }
+
+    pub fn encryption(mut self, encryption: EncryptionMode) -> Self {
+        self.options.encryption = encryption;
+        self
+    }
+
+    pub fn on_progress(mut self, on_progress: impl Fn(ParseProgress) + Send + Sync + 'static) -> Self {
+        self.options.on_progress = Some(Arc::new(on_progress));
+        self
+    }
+
+    pub fn build(self) -> ParseOptions {
+        self.options
+    }
+}
+
+/// Main function to parse a JWPUB file and export it to a target directory.
+/// `ParseOptions::default()` extracts the guessed document class with its images.
+#[cfg(feature = "native")]
+pub fn parse_jwpub<P: AsRef<Path>>(jwpub_path: P, output_dir: P, options: &ParseOptions) -> Result<Manifest> {
+    parse_jwpub_ex(jwpub_path, output_dir, options).map(|output| output.manifest)
+}
+
+/// Same as `parse_jwpub`, but honours `options` and also returns the list of asset files written to disk
+#[cfg(feature = "native")]
+pub fn parse_jwpub_ex<P: AsRef<Path>>(jwpub_path: P, output_dir: P, options: &ParseOptions) -> Result<ParseOutput> {
+    parse_jwpub_reader(File::open(jwpub_path)?, output_dir, options)
+}
+
+/// Same as `parse_jwpub_ex`, reading the JWPUB from any `Read + Seek` source instead of a path,
+/// e.g. a `Cursor` over an upload, so it never has to be written to disk first
+#[cfg(feature = "native")]
+pub fn parse_jwpub_reader<R: Read + Seek, P: AsRef<Path>>(reader: R, output_dir: P, options: &ParseOptions) -> Result<ParseOutput> {
+    if !options.extract_assets {
+        return parse_archive(reader, options, &mut MemoryAssetSink::default());
+    }
 
-/// Main function to parse a JWPUB file and export it to a target directory
-pub fn parse_jwpub<P: AsRef<Path>>(jwpub_path: P, output_dir: P) -> Result<Manifest> {
     let output_dir = output_dir.as_ref();
-    let assets_dir = output_dir.join("assets");
-    fs::create_dir_all(&assets_dir)?;
+    let mut sink = FsAssetSink::new(output_dir.join("assets"))?;
+    parse_archive(reader, options, &mut sink)
+}
 
-    // 1. Open JWPUB (ZIP)
-    let file = File::open(&jwpub_path)?;
-    let mut archive = ZipArchive::new(file)?;
+/// Outcome of one file processed by `parse_jwpub_dir`
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub struct BatchItem {
+    pub input: PathBuf,
+    /// Directory the publication was written to, with its parse output
+    pub result: Result<(PathBuf, ParseOutput)>,
+}
 
-    // 2. Extract 'contents' file (which is another ZIP)
-    let mut contents_zip_buffer = Vec::new();
-    {
-        let mut contents_file = archive.by_name("contents")
-            .map_err(|_| anyhow!("'contents' file not found in JWPUB"))?;
-        contents_file.read_to_end(&mut contents_zip_buffer)?;
+/// Parses every `.jwpub` under `input_dir` (recursively, in file name order) into its own
+/// `<output_dir>/<symbol>_<year>[_<issue>]` subdirectory, suffixed with `-2`, `-3`, ... when
+/// that directory already exists. A file that fails does not stop the batch; its error is
+/// reported in the returned list instead.
+#[cfg(feature = "native")]
+pub fn parse_jwpub_dir<P: AsRef<Path>>(input_dir: P, output_dir: P, options: &ParseOptions) -> Result<Vec<BatchItem>> {
+    let output_dir = output_dir.as_ref();
+    fs::create_dir_all(output_dir)?;
+
+    let mut inputs = Vec::new();
+    for entry in walkdir::WalkDir::new(input_dir).sort_by_file_name() {
+        let entry = entry.map_err(|e| JwpubError::Io(e.into()))?;
+        let is_jwpub = entry.path().extension().is_some_and(|ext| ext.eq_ignore_ascii_case("jwpub"));
+        if entry.file_type().is_file() && is_jwpub {
+            inputs.push(entry.into_path());
+        }
     }
 
-    // 3. Open Inner ZIP
-    let contents_cursor = std::io::Cursor::new(contents_zip_buffer);
-    let mut contents_archive = ZipArchive::new(contents_cursor)?;
+    let mut used_names = HashSet::new();
+    let mut items = Vec::new();
+    for (index, input) in inputs.into_iter().enumerate() {
+        log::info!("Parsing {:?}", input);
+        // The publication's name is only known once parsed, so it is written to a staging
+        // directory first and renamed afterwards
+        let staging = output_dir.join(format!(".partial-{}", index));
+        if staging.exists() {
+            log::warn!("Removing {:?} left over by an interrupted batch", staging);
+            if let Err(e) = fs::remove_dir_all(&staging) {
+                items.push(BatchItem { input, result: Err(e.into()) });
+                continue;
+            }
+        }
+        let result = parse_into_staging(&input, &staging, options).and_then(|mut output| {
+            let manifest = &output.manifest;
+            let mut name = format!(
+                "{}_{}",
+                assets::sanitize_path_component(&manifest.publication),
+                manifest.year
+            );
+            let issue = assets::sanitize_path_component(&manifest.issue);
+            if !issue.is_empty() && issue != "0" {
+                name = format!("{}_{}", name, issue);
+            }
+            // Directories from earlier runs are kept; this one gets a suffix instead
+            let final_dir = loop {
+                let final_dir = output_dir.join(assets::unique_file_name(&name, &mut used_names));
+                if !final_dir.exists() {
+                    break final_dir;
+                }
+            };
 
-    // 4. Extract SQLite Database
-    let db_name = contents_archive.file_names()
-        .find(|name| name.ends_with(".db"))
-        .map(|name| name.to_string())
-        .ok_or_else(|| anyhow!("Database file not found in contents"))?;
+            let staging = fs::canonicalize(&staging)?;
+            fs::rename(&staging, &final_dir)?;
+            let final_dir = fs::canonicalize(&final_dir)?;
+
+            for asset in &mut output.extracted_assets {
+                if let Ok(relative) = asset.path.strip_prefix(&staging) {
+                    asset.path = final_dir.join(relative);
+                }
+            }
+            Ok((final_dir, output))
+        });
 
-    let db_path = output_dir.join("temp.db");
-    {
-        let mut db_file_in_zip = contents_archive.by_name(&db_name)?;
-        let mut db_file_on_disk = File::create(&db_path)?;
-        std::io::copy(&mut db_file_in_zip, &mut db_file_on_disk)?;
+        if let Err(e) = &result {
+            let _ = fs::remove_dir_all(&staging);
+            log::warn!("Failed to parse {:?}: {}", input, e);
+        }
+        items.push(BatchItem { input, result });
     }
 
-    // 5. Initialize Services
-    let db_service = DatabaseService::from_file(&db_path)?;
-    let crypto_service = CryptoService::new()?;
+    Ok(items)
+}
 
-    // 6. Get Metadata & Keys
-    let pub_data = db_service.get_publication_data()?;
-    let pub_card = format!("{}_{}_{}_{}", 
-        pub_data.meps_language_index,
-        pub_data.symbol,
-        pub_data.year,
-        pub_data.issue_tag_number
-    );
-    println!("DEBUG: Derived PubCard: {}", pub_card);
-    let (key, iv) = crypto_service.derive_keys(&pub_card);
+#[cfg(feature = "native")]
+fn parse_into_staging(input: &Path, staging: &Path, options: &ParseOptions) -> Result<ParseOutput> {
+    fs::create_dir_all(staging)?;
+    parse_jwpub_ex(input, staging, options)
+}
+
+/// Parses a JWPUB handing every asset to `sink` instead of the filesystem
+/// (e.g. to push images straight into object storage)
+#[cfg(feature = "native")]
+pub fn parse_jwpub_with_sink<P: AsRef<Path>>(jwpub_path: P, options: &ParseOptions, sink: &mut dyn AssetSink) -> Result<ParseOutput> {
+    let file = File::open(jwpub_path)?;
+    parse_archive(file, options, sink)
+}
+
+/// Parses a JWPUB held in memory, without touching the filesystem or the network.
+/// Returns the manifest plus every asset as `(file_name, bytes)`; usable from wasm32 builds.
+pub fn parse_jwpub_bytes(data: &[u8]) -> Result<(Manifest, Vec<InMemoryAsset>)> {
+    let mut sink = MemoryAssetSink::default();
+    let output = parse_archive(Cursor::new(data), &ParseOptions::default(), &mut sink)?;
+    Ok((output.manifest, sink.assets))
+}
 
-    // 7. Determine Class ID based on publication type
-    // MWB = 106, W = 40. We can guess based on symbol
-    let class_id = if pub_data.symbol.to_lowercase().contains("mwb") { 106 } else { 40 };
+/// Shared pipeline behind every parse entry point
+fn parse_archive<R: Read + Seek>(reader: R, options: &ParseOptions, sink: &mut dyn AssetSink) -> Result<ParseOutput> {
+    let started = chrono::Utc::now();
+    let mut timings = StageTimings::default();
+
+    // 1-6. Open archives, database and derive keys
+    options.report(ParseStage::Unzipping, 0, 0);
+    let OpenedJwpub {
+        mut contents_archive,
+        db_name,
+        db_service,
+        crypto_service,
+        pub_data,
+        pub_card,
+        key,
+        iv,
+        ..
+    } = open_jwpub(reader)?;
+    timings.unzipping = elapsed_since(started);
+
+    // 7. Determine Class IDs
+    let class_ids = select_class_ids(&db_service, &pub_data, options)?;
 
     // 8. Process Documents
-    let raw_docs = db_service.get_documents_by_class(class_id)?;
-    let mut documents = Vec::new();
+    let raw_docs = select_documents(&db_service, &class_ids, options)?;
+    log::info!("Selected {} documents with content", raw_docs.len());
 
-    for (id, title, encrypted_content) in raw_docs {
-        if encrypted_content.is_empty() { continue; }
+    // Fail fast on a wrong key instead of reporting every document as broken. A few samples
+    // spread over the publication are tried so a run of corrupt documents does not stop it;
+    // with `skip_failed` every document reports its own failure instead.
+    let reader = ContentReader { crypto: &crypto_service, key: &key, iv: &iv, mode: options.encryption };
+    let sample_step = raw_docs.len().div_ceil(KEY_CHECK_SAMPLES).max(1);
+    let key_works = options.skip_failed
+        || raw_docs
+            .iter()
+            .step_by(sample_step)
+            .any(|(_, row)| crypto_service.verify_content(&row.content, &key, &iv, options.encryption));
+    if !raw_docs.is_empty() && !key_works {
+        return Err(JwpubError::KeyMismatch { pub_card });
+    }
 
-        let html_raw = crypto_service.decrypt_and_inflate(&encrypted_content, &key, &iv)?;
-        let (html, references, assets, paragraphs) = HtmlParser::parse(&html_raw);
+    let total = raw_docs.len();
+    let completed = AtomicUsize::new(0);
+    let documents_started = chrono::Utc::now();
 
-        documents.push(Document {
-            id,
-            title,
-            html,
-            references,
-            assets,
-            paragraphs,
-        });
+    // Documents are independent, so they are decrypted and parsed in parallel.
+    // Collecting an indexed parallel iterator keeps the database order, so the
+    // manifest is identical to a sequential run.
+    let results = raw_docs
+        .into_par_iter()
+        .map(|(class, row)| -> Result<std::result::Result<(Document, Vec<InMemoryAsset>), DocumentError>> {
+            let id = row.id;
+            options.report(ParseStage::Decrypting, completed.load(Ordering::Relaxed), total);
+            let html_raw = match reader.read_content(&row.content) {
+                Ok(html_raw) => {
+                    log::debug!("Decrypted document {} ({} bytes of HTML)", id, html_raw.len());
+                    html_raw
+                }
+                Err(e) if options.skip_failed => {
+                    log::warn!("Skipping document {}: {}", id, e);
+                    completed.fetch_add(1, Ordering::Relaxed);
+                    return Ok(Err(DocumentError { id, reason: e.to_string() }));
+                }
+                Err(source) => return Err(JwpubError::DecryptionFailed { doc_id: id, source }),
+            };
+
+            options.report(ParseStage::ParsingHtml, completed.load(Ordering::Relaxed), total);
+            let document = build_document_with_files(class, &row, &html_raw, options.rewrite_policy);
+            completed.fetch_add(1, Ordering::Relaxed);
+
+            Ok(Ok(document))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut documents = Vec::new();
+    let mut embedded_files = Vec::new();
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok((document, files)) => {
+                documents.push(document);
+                embedded_files.extend(files);
+            }
+            Err(error) => errors.push(error),
+        }
     }
+    timings.documents = elapsed_since(documents_started);
 
     // 9. Extract Physical Assets (Images)
-    for i in 0..contents_archive.len() {
+    let assets_started = chrono::Utc::now();
+    let mut content_addressed = None;
+    let sink: &mut dyn AssetSink = match options.asset_naming {
+        AssetNaming::Original => sink,
+        AssetNaming::ContentAddressed => content_addressed.insert(ContentAddressedSink::new(sink)),
+    };
+    let mut extracted_assets = Vec::new();
+    let mut used_names = HashSet::new();
+    // Probed image headers, keyed by the file name each image was written under
+    let mut image_info = HashMap::new();
+    // Images written under another name (corrected extension, hashed path), from the original file name
+    let mut renamed_assets = HashMap::new();
+    let entry_count = if options.extract_assets { contents_archive.len() } else { 0 };
+    for i in 0..entry_count {
+        options.report(ParseStage::ExtractingAssets, i, entry_count);
         let mut file = contents_archive.by_index(i)?;
         let name = file.name().to_string();
         
         if name.ends_with(".jpg") || name.ends_with(".png") || name.ends_with(".jpeg") {
-            let mut out_file = File::create(assets_dir.join(Path::new(&name).file_name().unwrap()))?;
-            std::io::copy(&mut file, &mut out_file)?;
+            // Absolute paths and `..` components only show up in crafted archives
+            if file.enclosed_name().is_none() {
+                log::warn!("Skipping archive entry '{}': unsafe path", name);
+                continue;
+            }
+            let original_name = match Path::new(&name).file_name().and_then(|n| n.to_str()) {
+                Some(original_name) => original_name.to_string(),
+                None => continue,
+            };
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+
+            // Some images carry the wrong extension (a PNG named `.jpg`); browsers go by it
+            let corrected_name = assets::correct_extension(&original_name, &bytes);
+            let file_name = assets::unique_file_name(&corrected_name, &mut used_names);
+            if corrected_name != original_name {
+                log::warn!("'{}' is not what its extension says, writing it as '{}'", name, file_name);
+                renamed_assets.entry(original_name.clone()).or_insert_with(|| file_name.clone());
+            }
+
+            if let Some(info) = assets::probe_image(&bytes) {
+                image_info.entry(file_name.clone()).or_insert(info);
+            }
+            sink.write_asset(&file_name, &mut bytes.as_slice())?;
+
+            extracted_assets.push(ExtractedAsset {
+                source_path: name,
+                path: sink.location(&file_name),
+                size: bytes.len() as u64,
+                mime_type: assets::mime_type_for(&file_name).to_string(),
+            });
         }
     }
 
-    // Cleanup
-    let _ = fs::remove_file(db_path);
+    // Some images only exist as encrypted blobs in the database
+    let mut cover_file_name = None;
+    if options.extract_assets {
+        extracted_assets.extend(extract_db_images(&db_service, &reader, &mut used_names, &mut image_info, &mut renamed_assets, sink)?);
+
+        if let Some((file_name, cover_asset)) = extract_cover(&db_service, &mut contents_archive, &reader, &mut used_names, sink)? {
+            cover_file_name = Some(file_name);
+            extracted_assets.push(cover_asset);
+        }
+
+        // `data:` images and inline SVG; documents sharing one embed share the file
+        for (file_name, bytes) in embedded_files {
+            if !used_names.insert(file_name.clone()) { continue; }
+            if let Some(info) = assets::probe_image(&bytes) {
+                image_info.entry(file_name.clone()).or_insert(info);
+            }
+            sink.write_asset(&file_name, &mut bytes.as_slice())?;
+
+            extracted_assets.push(ExtractedAsset {
+                path: sink.location(&file_name),
+                size: bytes.len() as u64,
+                mime_type: assets::mime_type_for(&file_name).to_string(),
+                source_path: file_name,
+            });
+        }
+    }
+
+    for asset in documents.iter_mut().flat_map(|document| document.assets.iter_mut()) {
+        if asset.r#type != AssetType::Image { continue; }
+        let file_name = renamed_assets.get(&asset.file_name).unwrap_or(&asset.file_name);
+        if let Some(info) = image_info.get(file_name) {
+            asset.width = Some(info.width);
+            asset.height = Some(info.height);
+            asset.mime = Some(info.mime.to_string());
+        }
+    }
+
+    // Content-addressed files replace the names everything was written under
+    if let Some(content_addressed) = content_addressed {
+        let hashed_names = content_addressed.into_names();
+        for file_name in renamed_assets.values_mut() {
+            if let Some(hashed) = hashed_names.get(file_name) {
+                *file_name = hashed.clone();
+            }
+        }
+        for (file_name, hashed) in &hashed_names {
+            renamed_assets.entry(file_name.clone()).or_insert_with(|| hashed.clone());
+        }
+        cover_file_name = cover_file_name.map(|file_name| hashed_names.get(&file_name).cloned().unwrap_or(file_name));
+    }
+    let cover = cover_file_name.map(|file_name| format!("./assets/{}", file_name));
+
+    log::info!("Extracted {} assets", extracted_assets.len());
+
+    // Images the documents point to that no extracted file provides
+    let mut missing_assets = Vec::new();
+    if options.extract_assets {
+        let available: HashSet<&str> = extracted_assets
+            .iter()
+            .map(|asset| asset.source_path.rsplit('/').next().unwrap_or(&asset.source_path))
+            .collect();
+        for asset in documents.iter().flat_map(|document| document.assets.iter()) {
+            if asset.r#type == AssetType::Image
+                && !available.contains(asset.file_name.as_str())
+                && !missing_assets.contains(&asset.file_name)
+            {
+                log::warn!("Image '{}' is referenced but not found in the archive", asset.file_name);
+                missing_assets.push(asset.file_name.clone());
+            }
+        }
+    }
+
+    // Point the documents at the images written under a corrected extension or hashed path
+    if !renamed_assets.is_empty() {
+        for document in &mut documents {
+            let mut references_renamed = false;
+            for asset in &mut document.assets {
+                if let Some(file_name) = renamed_assets.get(&asset.file_name) {
+                    asset.file_name = file_name.clone();
+                    references_renamed = true;
+                }
+            }
+            if references_renamed {
+                document.html = HtmlParser::rewrite_image_sources(&document.html, |src| {
+                    let file_name = renamed_assets.get(src.strip_prefix("./assets/")?)?;
+                    Some(format!("./assets/{}", file_name))
+                });
+            }
+        }
+    }
+    timings.extracting_assets = elapsed_since(assets_started);
+
+    let language = resolve_language(&db_service, pub_data.meps_language_index, &db_name);
+    let language_info = models::language_info(pub_data.meps_language_index)
+        .or_else(|| models::language_info_for_symbol(&language));
+    let direction = resolve_direction(&db_service, pub_data.meps_language_index, &language);
+    let title = pub_data.display_title();
+    let publication_type = pub_data.publication_type();
+    // The table of contents is only navigation aid, so a schema it cannot read is not fatal
+    let toc = db_service.get_toc().unwrap_or_else(|e| {
+        log::warn!("Could not read the table of contents: {}", e);
+        Vec::new()
+    });
+    let schema_version = db_service.schema_version().unwrap_or_else(|e| {
+        log::warn!("Could not read the schema version: {}", e);
+        None
+    });
+    log::info!("Database schema version: {}", schema_version.as_deref().unwrap_or("unknown"));
+    let date_range = db_service.get_date_range().unwrap_or_else(|e| {
+        log::warn!("Could not read the dated text range: {}", e);
+        None
+    });
+    drop(db_service);
 
     // 10. Build Manifest
     let manifest = Manifest {
         publication: pub_data.symbol,
         year: pub_data.year as u16,
-        issue: pub_data.issue_tag_number.to_string(), // Simplified
-        language: pub_data.meps_language_index.to_string(), // Simplified
-        title: format!("Parsed Publication"),
+        issue_info: IssueInfo::parse(&pub_data.issue_tag_number),
+        issue: pub_data.issue_tag_number,
+        date_range,
+        publication_type,
+        schema_version,
+        language,
+        language_name: language_info.map(|info| info.name.to_string()),
+        language_native_name: language_info.map(|info| info.native_name.to_string()),
+        direction,
+        title,
         extracted_at: chrono::Utc::now().to_rfc3339(),
         documents,
+        errors,
+        toc,
+        cover,
+        missing_assets,
     };
 
-    Ok(manifest)
+    timings.total = elapsed_since(started);
+    let stats = ParseStats { timings, class_ids, ..ParseStats::count(&manifest) };
+
+    Ok(ParseOutput { manifest, extracted_assets, stats })
+}
+
+/// Time since `start`. Measured with the wall clock rather than `Instant`, which panics on
+/// wasm32-unknown-unknown.
+fn elapsed_since(start: chrono::DateTime<chrono::Utc>) -> Duration {
+    (chrono::Utc::now() - start).to_std().unwrap_or_default()
+}
+
+/// Decrypts every selected document and returns `(document_id, raw_html)` pairs without
+/// running the HTML parser. Uses the same key derivation and class selection as `parse_jwpub_ex`,
+/// which makes it handy to inspect exactly what the parser receives.
+#[cfg(feature = "native")]
+pub fn decrypt_documents<P: AsRef<Path>>(jwpub_path: P, options: &ParseOptions) -> Result<Vec<(u32, String)>> {
+    let opened = open_jwpub(File::open(jwpub_path)?)?;
+    let class_ids = select_class_ids(&opened.db_service, &opened.pub_data, options)?;
+
+    let mut documents = Vec::new();
+    for (_class, row) in select_documents(&opened.db_service, &class_ids, options)? {
+        let html_raw = opened.crypto_service.read_content(&row.content, &opened.key, &opened.iv, options.encryption)
+            .map_err(|source| JwpubError::DecryptionFailed { doc_id: row.id, source })?;
+        documents.push((row.id, html_raw));
+    }
+
+    Ok(documents)
+}
+
+/// Runs the integrity checks of a JWPUB (ZIP, `contents`, database, `Publication` row, one
+/// sample document decrypting) and reports each of them instead of stopping at the first
+/// failure. Only fails when the file cannot be read at all.
+#[cfg(feature = "native")]
+pub fn validate_jwpub<P: AsRef<Path>>(jwpub_path: P) -> Result<ValidationReport> {
+    let data = fs::read(jwpub_path)?;
+    let mut report = ValidationReport::default();
+
+    let archive = data.starts_with(b"PK").then(|| ZipArchive::new(Cursor::new(data.as_slice())).ok()).flatten();
+    let Some(mut archive) = archive else {
+        report.problems.push(format!("Not a ZIP (detected: {})", sniff_file_type(&data[..data.len().min(8)])));
+        return Ok(report);
+    };
+    if is_epub(&mut archive) {
+        report.problems.push("The file is an EPUB, not a JWPUB".to_string());
+        return Ok(report);
+    }
+    report.is_zip = true;
+
+    let contents_archive = read_contents_archive(&mut archive)
+        .and_then(|(_, buffer)| ZipArchive::new(Cursor::new(buffer)).map_err(JwpubError::NotAZip));
+    let mut contents_archive = match contents_archive {
+        Ok(contents_archive) => contents_archive,
+        Err(e) => {
+            report.problems.push(e.to_string());
+            return Ok(report);
+        }
+    };
+    report.has_contents = true;
+
+    let Some(db_name) = contents_archive.file_names().find(|name| name.ends_with(".db")).map(|name| name.to_string()) else {
+        report.problems.push(JwpubError::DatabaseMissing.to_string());
+        return Ok(report);
+    };
+    let mut db_bytes = Vec::new();
+    contents_archive.by_name(&db_name)?.read_to_end(&mut db_bytes)?;
+    report.database_name = Some(db_name);
+    let db_service = match DatabaseService::from_bytes(&db_bytes) {
+        Ok(db_service) => db_service,
+        Err(e) => {
+            report.problems.push(format!("The database does not open: {}", e));
+            return Ok(report);
+        }
+    };
+    report.has_database = true;
+
+    match db_service.get_publication_count() {
+        Ok(1) => report.has_publication = true,
+        Ok(count) => report.problems.push(format!("The Publication table has {} rows instead of 1", count)),
+        Err(e) => report.problems.push(format!("The Publication table cannot be read: {}", e)),
+    }
+    if !report.has_publication {
+        return Ok(report);
+    }
+
+    // Keys come from the same path as a real parse, manifest overrides included
+    let opened = match open_jwpub(Cursor::new(data.as_slice())) {
+        Ok(opened) => opened,
+        Err(e) => {
+            report.problems.push(e.to_string());
+            return Ok(report);
+        }
+    };
+    let sample = match opened.db_service.get_first_document_id() {
+        Ok(Some(id)) => opened.db_service.get_document(id).map(|row| row.map(|row| (id, row.content))),
+        Ok(None) => Ok(None),
+        Err(e) => Err(e),
+    };
+    match sample {
+        Ok(Some((id, content))) => {
+            report.sample_document_id = Some(id);
+            report.sample_decrypts = opened.crypto_service.verify_content(&content, &opened.key, &opened.iv, EncryptionMode::Auto);
+            if !report.sample_decrypts {
+                report.problems.push(format!("Document {} does not decrypt with pub card {}", id, opened.pub_card));
+            }
+        }
+        Ok(None) => report.problems.push("No document has content to decrypt".to_string()),
+        Err(e) => report.problems.push(format!("The Document table cannot be read: {}", e)),
+    }
+
+    Ok(report)
+}
+
+/// Writes a copy of the JWPUB at `original` to `out` with the content of `documents` stored
+/// back in its database, zlib-compressed and encrypted with the keys of the publication.
+/// Documents are matched by id and written from their `html_raw`; every other entry is copied
+/// untouched. Returns how many documents were replaced.
+///
+/// This is a starting point for round-tripping edited publications: the hashes and
+/// timestamps in `manifest.json` are not recomputed.
+#[cfg(feature = "native")]
+pub fn repackage_jwpub<P: AsRef<Path>, Q: AsRef<Path>>(original: P, documents: &[Document], out: Q) -> Result<usize> {
+    let data = fs::read(original)?;
+    let OpenedJwpub { mut contents_archive, contents_name, db_name, crypto_service, key, iv, .. } =
+        open_jwpub(Cursor::new(data.as_slice()))?;
+
+    let mut db_bytes = Vec::new();
+    contents_archive.by_name(&db_name)?.read_to_end(&mut db_bytes)?;
+    let db_service = DatabaseService::from_bytes_writable(&db_bytes).map_err(JwpubError::Database)?;
+    let mut replaced = 0;
+    for document in documents {
+        let content = crypto_service.encrypt_and_deflate(&document.html_raw, &key, &iv)?;
+        if db_service.set_document_content(document.id, &content).map_err(JwpubError::Database)? {
+            replaced += 1;
+        } else {
+            log::warn!("Document {} is not in the publication, skipping it", document.id);
+        }
+    }
+    let db_bytes = db_service.to_bytes().map_err(JwpubError::Database)?;
+
+    let contents_bytes = copy_archive_replacing(&mut contents_archive, &db_name, &db_bytes, Cursor::new(Vec::new()))?.into_inner();
+    let mut archive = ZipArchive::new(Cursor::new(data.as_slice()))?;
+    copy_archive_replacing(&mut archive, &contents_name, &contents_bytes, File::create(out)?)?;
+
+    Ok(replaced)
+}
+
+/// Copies every entry of `archive` into a new archive written to `writer`, storing `data`
+/// under `name` instead of the original entry
+#[cfg(feature = "native")]
+fn copy_archive_replacing<R: Read + Seek, W: std::io::Write + Seek>(archive: &mut ZipArchive<R>, name: &str, data: &[u8], writer: W) -> Result<W> {
+    let mut zip = zip::ZipWriter::new(writer);
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        if entry.name() == name {
+            zip.start_file(name, zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated))?;
+            std::io::Write::write_all(&mut zip, data)?;
+        } else {
+            zip.raw_copy_file(entry)?;
+        }
+    }
+    Ok(zip.finish()?)
+}
+
+/// Lists every document of the publication (all classes) along with its metadata, reading
+/// only the database: nothing is decrypted and no asset is extracted, which makes it cheap
+/// enough to index many files.
+#[cfg(feature = "native")]
+pub fn list_documents<P: AsRef<Path>>(jwpub_path: P) -> Result<(DbPublication, Vec<DocumentSummary>)> {
+    let opened = open_jwpub(File::open(jwpub_path)?)?;
+    let documents = opened.db_service.get_document_summaries().map_err(JwpubError::Database)?;
+
+    Ok((opened.pub_data, documents))
+}
+
+/// Dumps the `Publication` row and the first `document_limit` `Document` rows of the database,
+/// without deriving keys or decrypting anything, so it also works on files that fail to parse
+#[cfg(feature = "native")]
+pub fn dump_database<P: AsRef<Path>>(jwpub_path: P, document_limit: usize) -> Result<DatabaseDump> {
+    let mut archive = ZipArchive::new(File::open(jwpub_path)?).map_err(JwpubError::NotAZip)?;
+    let (_, contents_zip_buffer) = read_contents_archive(&mut archive)?;
+    let mut contents_archive = ZipArchive::new(Cursor::new(contents_zip_buffer)).map_err(JwpubError::NotAZip)?;
+
+    let db_name = contents_archive.file_names()
+        .find(|name| name.ends_with(".db"))
+        .map(|name| name.to_string())
+        .ok_or(JwpubError::DatabaseMissing)?;
+    let mut db_bytes = Vec::new();
+    contents_archive.by_name(&db_name)?.read_to_end(&mut db_bytes)?;
+
+    let db_service = DatabaseService::from_bytes(&db_bytes).map_err(JwpubError::Database)?;
+    db_service.dump(document_limit).map_err(JwpubError::Database)
+}
+
+/// Decrypts and parses a single document by MepsDocumentId, without touching the rest of the
+/// publication (e.g. to load articles on demand). Returns `None` when the id does not exist or
+/// the document has no content.
+#[cfg(feature = "native")]
+pub fn parse_single_document<P: AsRef<Path>>(jwpub_path: P, doc_id: u32) -> Result<Option<Document>> {
+    let opened = open_jwpub(File::open(jwpub_path)?)?;
+    let row = match opened.db_service.get_document(doc_id).map_err(JwpubError::Database)? {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    if row.content.is_empty() {
+        return Ok(None);
+    }
+    let class = opened.db_service.get_document_class(doc_id).map_err(JwpubError::Database)?.unwrap_or_default();
+
+    let html_raw = opened.crypto_service.read_content(&row.content, &opened.key, &opened.iv, EncryptionMode::Auto)
+        .map_err(|source| JwpubError::DecryptionFailed { doc_id, source })?;
+
+    Ok(Some(build_document(class, &row, &html_raw, RewritePolicy::default())))
+}
+
+/// Runs the HTML parser over a decrypted document
+pub(crate) fn build_document(class: i32, row: &DocumentRow, html_raw: &str, policy: RewritePolicy) -> Document {
+    build_document_with_files(class, row, html_raw, policy).0
+}
+
+/// Same as `build_document`, also returning the files embedded in the HTML
+/// (`data:` images and inline SVG) for the caller to write out
+fn build_document_with_files(class: i32, row: &DocumentRow, html_raw: &str, policy: RewritePolicy) -> (Document, Vec<InMemoryAsset>) {
+    let parsed = HtmlParser::parse_with_policy(html_raw, policy);
+    let document = Document {
+        id: row.id,
+        class,
+        title: row.display_title(),
+        context_title: row.context_title.clone(),
+        feature_title: row.feature_title.clone(),
+        html: parsed.html,
+        html_raw: parsed.html_raw,
+        references: parsed.references,
+        assets: parsed.assets,
+        paragraphs: parsed.paragraphs,
+        footnotes: parsed.footnotes,
+        headings: parsed.headings,
+        questions: parsed.questions,
+        content_blocks: parsed.content_blocks,
+        plain_text: parsed.plain_text,
+        content_hash: hex::encode(Sha256::digest(html_raw.as_bytes())),
+    };
+    (document, parsed.embedded_files)
+}
+
+/// Everything needed to read a publication once its archives are open and its keys derived
+struct OpenedJwpub {
+    contents_archive: ZipArchive<Cursor<Vec<u8>>>,
+    /// Entries of the outer archive and of `contents_archive` holding the contents and database.
+    /// The contents name is only needed to repackage the file.
+    #[cfg(feature = "native")]
+    contents_name: String,
+    db_name: String,
+    db_service: DatabaseService,
+    crypto_service: CryptoService,
+    pub_data: DbPublication,
+    pub_card: String,
+    key: Vec<u8>,
+    iv: Vec<u8>,
+}
+
+/// Opens the JWPUB, loads its database in memory and derives the decryption keys
+fn open_jwpub<R: Read + Seek>(mut reader: R) -> Result<OpenedJwpub> {
+    // Catch the common mistake of passing some other kind of file before zip fails cryptically
+    let mut header = Vec::new();
+    reader.by_ref().take(8).read_to_end(&mut header)?;
+    reader.seek(SeekFrom::Start(0))?;
+    if !header.starts_with(b"PK") {
+        return Err(JwpubError::NotAJwpub { detected: sniff_file_type(&header).to_string() });
+    }
+
+    // 1. Open JWPUB (ZIP)
+    let mut archive = ZipArchive::new(reader).map_err(JwpubError::NotAZip)?;
+    log::debug!("Opened JWPUB archive with {} entries", archive.len());
+    if is_epub(&mut archive) {
+        return Err(JwpubError::NotAJwpub { detected: "epub".to_string() });
+    }
+
+    // 2. Extract 'contents' file (which is another ZIP)
+    #[cfg_attr(not(feature = "native"), allow(unused_variables))]
+    let (contents_name, contents_zip_buffer) = read_contents_archive(&mut archive)?;
+
+    // 3. Open Inner ZIP
+    let contents_cursor = Cursor::new(contents_zip_buffer);
+    let mut contents_archive = ZipArchive::new(contents_cursor).map_err(JwpubError::NotAZip)?;
+    log::debug!("Opened contents archive with {} entries", contents_archive.len());
+
+    // The inner manifest describes the contents, so it wins over one next to them. A plaintext
+    // one is read right away; an encrypted one must wait for the keys derived from the database.
+    let manifest_bytes = read_manifest_bytes(&mut contents_archive)
+        .or_else(|| read_manifest_bytes(&mut archive));
+    let (mut archive_manifest, encrypted_manifest) = match manifest_bytes {
+        Some(bytes) => match serde_json::from_slice::<ArchiveManifest>(&bytes) {
+            Ok(manifest) => (manifest.publication, None),
+            Err(_) => (None, Some(bytes)),
+        },
+        None => (None, None),
+    };
+
+    // 4. Extract SQLite Database (the one the manifest names, if any)
+    let manifest_db = archive_manifest
+        .as_ref()
+        .and_then(|manifest| manifest.file_name.as_deref())
+        .filter(|file_name| contents_archive.file_names().any(|name| name == *file_name))
+        .map(|file_name| file_name.to_string());
+    let db_name = match manifest_db {
+        Some(db_name) => db_name,
+        None => contents_archive.file_names()
+            .find(|name| name.ends_with(".db"))
+            .map(|name| name.to_string())
+            .ok_or(JwpubError::DatabaseMissing)?,
+    };
+
+    let mut db_bytes = Vec::new();
+    contents_archive.by_name(&db_name)?.read_to_end(&mut db_bytes)?;
+    log::debug!("Extracted database '{}' ({} bytes)", db_name, db_bytes.len());
+
+    // 5. Initialize Services
+    let db_service = DatabaseService::from_bytes(&db_bytes).map_err(JwpubError::Database)?;
+    let crypto_service = CryptoService::new().map_err(JwpubError::KeyDerivation)?;
+
+    // 6. Get Metadata & Keys
+    let mut pub_data = db_service.get_publication_data().map_err(JwpubError::Database)?;
+    if let Some(bytes) = encrypted_manifest {
+        let (key, iv) = crypto_service.derive_keys(&pub_data.pub_card());
+        archive_manifest = decrypt_archive_manifest(&crypto_service, &bytes, &key, &iv);
+    }
+    if let Some(manifest) = &archive_manifest {
+        log::debug!("Using publication metadata from manifest.json: {:?}", manifest);
+        pub_data.apply_manifest(manifest);
+    }
+    log::info!("Publication {} ({}, issue {})", pub_data.symbol, pub_data.year, pub_data.issue_tag_number);
+    let pub_card = pub_data.pub_card();
+    log::debug!("Derived PubCard: {}", pub_card);
+    let (key, iv) = crypto_service.derive_keys(&pub_card);
+
+    Ok(OpenedJwpub {
+        contents_archive,
+        #[cfg(feature = "native")]
+        contents_name,
+        db_name,
+        db_service,
+        crypto_service,
+        pub_data,
+        pub_card,
+        key,
+        iv,
+    })
+}
+
+/// Decrypts the images stored as blobs in the database and hands them to `sink`, naming each
+/// after its database file name with the extension of the sniffed format. A name already taken
+/// gets a numeric suffix, and images written under another name than the one the documents use
+/// are recorded in `renamed_assets`. Blobs that fail to decrypt or are not a known image format are skipped.
+fn extract_db_images(
+    db_service: &DatabaseService,
+    reader: &ContentReader,
+    used_names: &mut HashSet<String>,
+    image_info: &mut HashMap<String, ImageInfo>,
+    renamed_assets: &mut HashMap<String, String>,
+    sink: &mut dyn AssetSink,
+) -> Result<Vec<ExtractedAsset>> {
+    let mut extracted_assets = Vec::new();
+    for (name, encrypted) in db_service.get_image_blobs().map_err(JwpubError::Database)? {
+        let bytes = match reader.read_blob(&encrypted) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                log::warn!("Skipping database image '{}': {}", name, e);
+                continue;
+            }
+        };
+        let extension = match assets::sniff_image_extension(&bytes) {
+            Some(extension) => extension,
+            None => {
+                log::warn!("Skipping database image '{}': unknown image format", name);
+                continue;
+            }
+        };
+
+        let path = Path::new(&name);
+        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+            log::warn!("Skipping database image '{}': no file name", name);
+            continue;
+        };
+        // A loose file already written under the original name keeps serving it
+        let original_name = path.file_name().and_then(|n| n.to_str()).filter(|n| !used_names.contains(*n));
+        let file_name = assets::unique_file_name(&format!("{}.{}", stem, extension), used_names);
+        if let Some(original_name) = original_name.filter(|n| *n != file_name) {
+            renamed_assets.entry(original_name.to_string()).or_insert_with(|| file_name.clone());
+        }
+
+        if let Some(info) = assets::probe_image(&bytes) {
+            image_info.entry(file_name.clone()).or_insert(info);
+        }
+        sink.write_asset(&file_name, &mut bytes.as_slice())?;
+
+        extracted_assets.push(ExtractedAsset {
+            source_path: name,
+            path: sink.location(&file_name),
+            size: bytes.len() as u64,
+            mime_type: assets::mime_type_for(&file_name).to_string(),
+        });
+    }
+
+    Ok(extracted_assets)
+}
+
+/// Keys of an opened publication together with how its content is stored
+struct ContentReader<'a> {
+    crypto: &'a CryptoService,
+    key: &'a [u8],
+    iv: &'a [u8],
+    mode: EncryptionMode,
+}
+
+impl ContentReader<'_> {
+    fn read_content(&self, data: &[u8]) -> anyhow::Result<String> {
+        self.crypto.read_content(data, self.key, self.iv, self.mode)
+    }
+
+    fn read_blob(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        self.crypto.read_blob(data, self.key, self.iv, self.mode)
+    }
+}
+
+/// Writes the publication cover (a loose file of the archive or a database blob) as
+/// `cover.<ext>`, returning that file name with its asset. `None` when the database names no
+/// cover or its file cannot be found.
+fn extract_cover<R: Read + Seek>(
+    db_service: &DatabaseService,
+    contents_archive: &mut ZipArchive<R>,
+    reader: &ContentReader,
+    used_names: &mut HashSet<String>,
+    sink: &mut dyn AssetSink,
+) -> Result<Option<(String, ExtractedAsset)>> {
+    let cover_name = match db_service.get_cover_file_name() {
+        Ok(Some(cover_name)) => cover_name,
+        Ok(None) => return Ok(None),
+        Err(e) => {
+            log::warn!("Could not look up the cover image: {}", e);
+            return Ok(None);
+        }
+    };
+    let base_name = |name: &str| name.rsplit('/').next().unwrap_or(name).to_string();
+    let cover_base = base_name(&cover_name);
+
+    let entry = contents_archive.file_names().find(|name| base_name(name) == cover_base).map(|name| name.to_string());
+    let (source_path, bytes) = match entry {
+        Some(entry) => {
+            let mut bytes = Vec::new();
+            contents_archive.by_name(&entry)?.read_to_end(&mut bytes)?;
+            (entry, bytes)
+        }
+        None => {
+            let blob = db_service.get_image_blobs().map_err(JwpubError::Database)?
+                .into_iter()
+                .find(|(name, _)| base_name(name) == cover_base);
+            match blob.and_then(|(name, encrypted)| Some((name, reader.read_blob(&encrypted).ok()?))) {
+                Some(blob) => blob,
+                None => {
+                    log::warn!("Cover image '{}' not found in the archive", cover_name);
+                    return Ok(None);
+                }
+            }
+        }
+    };
+
+    let extension = assets::sniff_image_extension(&bytes)
+        .or_else(|| Path::new(&cover_base).extension().and_then(|e| e.to_str()))
+        .unwrap_or("jpg");
+    let file_name = assets::unique_file_name(&format!("cover.{}", extension), used_names);
+    sink.write_asset(&file_name, &mut bytes.as_slice())?;
+
+    let asset = ExtractedAsset {
+        source_path,
+        path: sink.location(&file_name),
+        size: bytes.len() as u64,
+        mime_type: assets::mime_type_for(&file_name).to_string(),
+    };
+    Ok(Some((file_name, asset)))
+}
+
+/// Names the kind of a file that is not a ZIP from its first bytes
+fn sniff_file_type(header: &[u8]) -> &'static str {
+    if let Some(extension) = assets::sniff_image_extension(header) {
+        return extension;
+    }
+    match header {
+        [] => "empty file",
+        [b'%', b'P', b'D', b'F', ..] => "pdf",
+        [b'S', b'Q', b'L', b'i', b't', b'e', ..] => "sqlite",
+        [0x1f, 0x8b, ..] => "gzip",
+        [b'R', b'a', b'r', b'!', ..] => "rar",
+        [b'7', b'z', 0xBC, 0xAF, ..] => "7z",
+        [b'<', ..] => "html/xml",
+        _ => "unknown",
+    }
+}
+
+/// EPUBs are ZIPs too; they are recognized by their `mimetype` entry or container file
+fn is_epub<R: Read + Seek>(archive: &mut ZipArchive<R>) -> bool {
+    if archive.by_name("META-INF/container.xml").is_ok() {
+        return true;
+    }
+    let mut mimetype = String::new();
+    archive
+        .by_name("mimetype")
+        .is_ok_and(|mut file| file.read_to_string(&mut mimetype).is_ok() && mimetype.trim() == "application/epub+zip")
+}
+
+/// Reads the inner archive: the entry named `contents` (any case, at any path), or else the
+/// first entry that is itself a ZIP holding a `.db`
+fn read_contents_archive<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<(String, Vec<u8>)> {
+    let names: Vec<String> = archive.file_names().map(|name| name.to_string()).collect();
+
+    let named = names.iter().find(|name| {
+        name.rsplit('/').next().is_some_and(|file_name| file_name.eq_ignore_ascii_case("contents"))
+    });
+    if let Some(name) = named {
+        let mut buffer = Vec::new();
+        archive.by_name(name)?.read_to_end(&mut buffer)?;
+        return Ok((name.clone(), buffer));
+    }
+
+    for name in &names {
+        let mut buffer = Vec::new();
+        archive.by_name(name)?.read_to_end(&mut buffer)?;
+
+        let holds_db = ZipArchive::new(Cursor::new(buffer.as_slice()))
+            .map(|inner| inner.file_names().any(|inner_name| inner_name.ends_with(".db")))
+            .unwrap_or(false);
+        if holds_db {
+            log::info!("Using '{}' as the contents archive", name);
+            return Ok((name.clone(), buffer));
+        }
+    }
+
+    Err(JwpubError::ContentsMissing { entries: names })
+}
+
+/// Raw `manifest.json` of an archive, if it has one
+fn read_manifest_bytes<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Option<Vec<u8>> {
+    let mut file = archive.by_name("manifest.json").ok()?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer).ok()?;
+    Some(buffer)
+}
+
+/// Publication metadata of a `manifest.json` that is encrypted and compressed like the
+/// documents. A manifest that still cannot be read is ignored.
+fn decrypt_archive_manifest(
+    crypto_service: &CryptoService,
+    bytes: &[u8],
+    key: &[u8],
+    iv: &[u8],
+) -> Option<ArchiveManifestPublication> {
+    let parsed = crypto_service
+        .decrypt_and_inflate(bytes, key, iv)
+        .and_then(|json| Ok(serde_json::from_str::<ArchiveManifest>(&json)?));
+
+    match parsed {
+        Ok(manifest) => manifest.publication,
+        Err(e) => {
+            log::warn!("Ignoring unreadable manifest.json: {}", e);
+            None
+        }
+    }
+}
+
+/// Human language code for a MEPS index: the database's `Language` table wins, then the
+/// bundled table, and finally the raw index as a string
+fn resolve_language(db_service: &DatabaseService, meps_index: i32, db_name: &str) -> String {
+    db_service
+        .get_language_symbol(meps_index)
+        .ok()
+        .flatten()
+        .or_else(|| models::language_symbol(meps_index).map(|symbol| symbol.to_string()))
+        .or_else(|| language_from_file_name(db_name))
+        .unwrap_or_else(|| meps_index.to_string())
+}
+
+/// Language symbol in a publication file name, which is laid out as `<symbol>_<language>[_<issue>]`
+/// (`w_A_202301.db` is Arabic). Covers the languages missing from the bundled MEPS table.
+fn language_from_file_name(file_name: &str) -> Option<String> {
+    let stem = Path::new(file_name).file_stem()?.to_str()?;
+    stem.split('_')
+        .nth(1)
+        .filter(|symbol| !symbol.is_empty() && symbol.len() <= 4 && symbol.bytes().all(|b| b.is_ascii_alphanumeric()))
+        .map(str::to_ascii_uppercase)
+}
+
+/// Reading direction from the `Language` table's flag when present, otherwise from the symbol
+fn resolve_direction(db_service: &DatabaseService, meps_index: i32, language: &str) -> TextDirection {
+    match db_service.get_language_is_rtl(meps_index).ok().flatten() {
+        Some(true) => TextDirection::Rtl,
+        Some(false) => TextDirection::Ltr,
+        None => models::language_direction(language),
+    }
+}
+
+/// Class of a document with its database row
+type RawDocument = (i32, DocumentRow);
+
+/// Documents with content in `class_ids`, class by class in `DocumentId` order, sliced by
+/// `ParseOptions::offset`/`limit`
+fn select_documents(db_service: &DatabaseService, class_ids: &[i32], options: &ParseOptions) -> Result<Vec<RawDocument>> {
+    let mut raw_docs = Vec::new();
+    for &class in class_ids {
+        for row in db_service.get_documents_by_class(class).map_err(JwpubError::Database)? {
+            if row.content.is_empty() { continue; }
+            raw_docs.push((class, row));
+        }
+    }
+
+    Ok(raw_docs
+        .into_iter()
+        .skip(options.offset)
+        .take(options.limit.unwrap_or(usize::MAX))
+        .collect())
+}
+
+/// Resolves which document classes to extract
+fn select_class_ids(db_service: &DatabaseService, pub_data: &DbPublication, options: &ParseOptions) -> Result<Vec<i32>> {
+    if options.all_classes {
+        return db_service.get_all_document_classes().map_err(JwpubError::Database);
+    }
+
+    if let Some(class_id) = options.class_id {
+        return Ok(vec![class_id]);
+    }
+
+    // The bundled table knows the content classes of the usual publications; for the rest
+    // everything is extracted rather than guessing a class that may not exist
+    let publication_type = pub_data.publication_type();
+    match models::content_classes(publication_type, &pub_data.symbol) {
+        Some(class_ids) => {
+            log::info!("Using document classes {:?} for {:?} '{}'", class_ids, publication_type, pub_data.symbol);
+            Ok(class_ids.to_vec())
+        }
+        None => {
+            log::info!("No known classes for {:?} '{}', extracting every class", publication_type, pub_data.symbol);
+            db_service.get_all_document_classes().map_err(JwpubError::Database)
+        }
+    }
 }
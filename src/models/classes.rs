@@ -0,0 +1,19 @@
+use super::PublicationType;
+
+/// `(type, symbol, classes)`: document classes holding the main content of a publication.
+/// A `None` symbol matches every publication of that type; exact symbols are tried first.
+const CONTENT_CLASSES: &[(PublicationType, Option<&str>, &[i32])] = &[
+    (PublicationType::MeetingWorkbook, None, &[106]),
+    (PublicationType::Magazine, Some("w"), &[40]),
+    (PublicationType::Magazine, Some("ws"), &[40]),
+];
+
+/// Returns the content classes of a publication, or `None` when it is not in the bundled table
+pub fn content_classes(publication_type: PublicationType, symbol: &str) -> Option<&'static [i32]> {
+    let exact = CONTENT_CLASSES.iter().find(|(kind, entry_symbol, _)| {
+        *kind == publication_type && entry_symbol.is_some_and(|entry_symbol| entry_symbol.eq_ignore_ascii_case(symbol))
+    });
+    let any_symbol = || CONTENT_CLASSES.iter().find(|(kind, entry_symbol, _)| *kind == publication_type && entry_symbol.is_none());
+
+    exact.or_else(any_symbol).map(|(_, _, classes)| *classes)
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::{Document, Manifest};
+
+/// What changed between two parses of a publication, from `Manifest::diff`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestDiff {
+    /// Ids of documents only in the newer manifest, in its order
+    pub added_documents: Vec<u32>,
+    /// Ids of documents only in the older manifest, in its order
+    pub removed_documents: Vec<u32>,
+    /// Ids of documents in both whose content, title or class differ
+    pub modified_documents: Vec<u32>,
+    /// Publication metadata that differs. `extracted_at` is ignored, as it always does.
+    pub metadata: Vec<FieldChange>,
+}
+
+impl ManifestDiff {
+    /// Whether both manifests describe the same content
+    pub fn is_empty(&self) -> bool {
+        self.added_documents.is_empty()
+            && self.removed_documents.is_empty()
+            && self.modified_documents.is_empty()
+            && self.metadata.is_empty()
+    }
+}
+
+/// A manifest field with different values, named as in `manifest.json`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChange {
+    pub field: String,
+    pub old: serde_json::Value,
+    pub new: serde_json::Value,
+}
+
+impl Manifest {
+    /// Compares this manifest (the older one) with `other`. Documents are matched by id and
+    /// compared through `content_hash`, falling back to the raw HTML when a hash is missing.
+    pub fn diff(&self, other: &Manifest) -> ManifestDiff {
+        let old_documents: HashMap<u32, &Document> = self.documents.iter().map(|document| (document.id, document)).collect();
+        let new_documents: HashMap<u32, &Document> = other.documents.iter().map(|document| (document.id, document)).collect();
+
+        let mut diff = ManifestDiff::default();
+        for document in &other.documents {
+            match old_documents.get(&document.id) {
+                None => diff.added_documents.push(document.id),
+                Some(old) if document_changed(old, document) => diff.modified_documents.push(document.id),
+                Some(_) => {}
+            }
+        }
+        diff.removed_documents = self
+            .documents
+            .iter()
+            .filter(|document| !new_documents.contains_key(&document.id))
+            .map(|document| document.id)
+            .collect();
+
+        let mut compare = |field: &str, old: serde_json::Value, new: serde_json::Value| {
+            if old != new {
+                diff.metadata.push(FieldChange { field: field.to_string(), old, new });
+            }
+        };
+        compare("publication", self.publication.as_str().into(), other.publication.as_str().into());
+        compare("year", self.year.into(), other.year.into());
+        compare("issue", self.issue.as_str().into(), other.issue.as_str().into());
+        compare("date_range", to_value(&self.date_range), to_value(&other.date_range));
+        compare("publication_type", to_value(&self.publication_type), to_value(&other.publication_type));
+        compare("schema_version", to_value(&self.schema_version), to_value(&other.schema_version));
+        compare("language", self.language.as_str().into(), other.language.as_str().into());
+        compare("language_name", to_value(&self.language_name), to_value(&other.language_name));
+        compare("direction", to_value(&self.direction), to_value(&other.direction));
+        compare("title", self.title.as_str().into(), other.title.as_str().into());
+        compare("cover", to_value(&self.cover), to_value(&other.cover));
+        compare("missing_assets", to_value(&self.missing_assets), to_value(&other.missing_assets));
+        compare("toc", to_value(&self.toc), to_value(&other.toc));
+
+        diff
+    }
+}
+
+fn document_changed(old: &Document, new: &Document) -> bool {
+    let content_changed = if old.content_hash.is_empty() || new.content_hash.is_empty() {
+        old.html_raw != new.html_raw
+    } else {
+        old.content_hash != new.content_hash
+    };
+    content_changed || old.title != new.title || old.class != new.class
+}
+
+fn to_value<T: Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or(serde_json::Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A manifest as written before the optional metadata fields were added
+    const OLD_MANIFEST: &str = r#"{
+        "publication": "w",
+        "year": 2023,
+        "issue": "20230100",
+        "language": "E",
+        "title": "The Watchtower",
+        "extracted_at": "2023-01-01T00:00:00Z",
+        "documents": []
+    }"#;
+
+    /// A manifest with one document as the first releases wrote it
+    const OLD_MANIFEST_WITH_DOCUMENT: &str = r#"{
+        "publication": "w",
+        "year": 2023,
+        "issue": "20230100",
+        "language": "E",
+        "title": "The Watchtower",
+        "extracted_at": "2023-01-01T00:00:00Z",
+        "documents": [{
+            "id": 1102023201,
+            "title": "Article",
+            "html": "<p>Hello <a href=\"bible://NWTR/E/43003016\">John 3:16</a></p>",
+            "references": [{ "type": "bible", "link": "bible://NWTR/E/43003016", "text": "John 3:16" }],
+            "assets": [{ "fileName": "photo.jpg", "altText": "Photo", "type": "image" }],
+            "paragraphs": ["Hello John 3:16"]
+        }]
+    }"#;
+
+    #[test]
+    fn loads_documents_of_a_manifest_without_the_newer_fields() {
+        let old: Manifest = serde_json::from_str(OLD_MANIFEST_WITH_DOCUMENT).unwrap();
+        let document = &old.documents[0];
+        assert_eq!(document.paragraph_texts(), ["Hello John 3:16"]);
+        assert_eq!(document.references[0].link, "bible://NWTR/E/43003016");
+        assert_eq!(document.assets[0].file_name, "photo.jpg");
+        assert!(document.html_raw.is_empty() && document.footnotes.is_empty());
+
+        let new: Manifest = serde_json::from_str(OLD_MANIFEST_WITH_DOCUMENT).unwrap();
+        assert!(old.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn diffs_against_a_manifest_without_the_newer_fields() {
+        let old: Manifest = serde_json::from_str(OLD_MANIFEST).unwrap();
+        let mut new: Manifest = serde_json::from_str(OLD_MANIFEST).unwrap();
+        assert!(old.diff(&new).is_empty());
+
+        new.cover = Some("./assets/cover.jpg".to_string());
+        let diff = old.diff(&new);
+        assert_eq!(diff.metadata.len(), 1);
+        assert_eq!(diff.metadata[0].field, "cover");
+    }
+}
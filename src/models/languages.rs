@@ -0,0 +1,99 @@
+use serde::Serialize;
+
+use super::TextDirection;
+
+/// A language publications ship in, as listed in the bundled language table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanguageInfo {
+    /// MEPS language index, `None` for the languages only known by their symbol
+    pub meps_index: Option<i32>,
+    /// JW language symbol, e.g. `E` or `S`
+    pub symbol: &'static str,
+    /// BCP-47 tag, e.g. `en` or `pt-BR`
+    pub code: &'static str,
+    /// Name in English
+    pub name: &'static str,
+    /// Name in the language itself
+    pub native_name: &'static str,
+    pub direction: TextDirection,
+}
+
+const fn language(meps_index: i32, symbol: &'static str, code: &'static str, name: &'static str, native_name: &'static str) -> LanguageInfo {
+    LanguageInfo { meps_index: Some(meps_index), symbol, code, name, native_name, direction: TextDirection::Ltr }
+}
+
+/// A language whose MEPS index is not bundled; publications in it are matched by symbol
+const fn by_symbol(symbol: &'static str, code: &'static str, name: &'static str, native_name: &'static str) -> LanguageInfo {
+    LanguageInfo { meps_index: None, symbol, code, name, native_name, direction: TextDirection::Ltr }
+}
+
+const fn rtl(language: LanguageInfo) -> LanguageInfo {
+    LanguageInfo { direction: TextDirection::Rtl, ..language }
+}
+
+/// Symbol, BCP-47 tag, names and reading direction of the languages publications ship in
+const LANGUAGES: &[LanguageInfo] = &[
+    language(0, "E", "en", "English", "English"),
+    language(1, "S", "es", "Spanish", "Español"),
+    language(2, "X", "de", "German", "Deutsch"),
+    language(3, "F", "fr", "French", "Français"),
+    language(4, "I", "it", "Italian", "Italiano"),
+    language(5, "T", "pt-BR", "Portuguese (Brazil)", "Português (Brasil)"),
+    language(6, "O", "nl", "Dutch", "Nederlands"),
+    language(7, "J", "ja", "Japanese", "日本語"),
+    by_symbol("TPO", "pt-PT", "Portuguese (Portugal)", "Português (Portugal)"),
+    by_symbol("KO", "ko", "Korean", "한국어"),
+    by_symbol("CHS", "zh-Hans", "Chinese (Simplified)", "中文简体"),
+    by_symbol("CH", "zh-Hant", "Chinese (Traditional)", "中文繁體"),
+    by_symbol("U", "ru", "Russian", "Русский"),
+    by_symbol("K", "uk", "Ukrainian", "Українська"),
+    by_symbol("P", "pl", "Polish", "Polski"),
+    by_symbol("B", "cs", "Czech", "Čeština"),
+    by_symbol("V", "sk", "Slovak", "Slovenčina"),
+    by_symbol("H", "hu", "Hungarian", "Magyar"),
+    by_symbol("M", "ro", "Romanian", "Română"),
+    by_symbol("BL", "bg", "Bulgarian", "Български"),
+    by_symbol("C", "hr", "Croatian", "Hrvatski"),
+    by_symbol("AL", "sq", "Albanian", "Shqip"),
+    by_symbol("G", "el", "Greek", "Ελληνικά"),
+    by_symbol("TK", "tr", "Turkish", "Türkçe"),
+    by_symbol("GE", "ka", "Georgian", "ქართული"),
+    by_symbol("Z", "sv", "Swedish", "Svenska"),
+    by_symbol("D", "da", "Danish", "Dansk"),
+    by_symbol("N", "no", "Norwegian", "Norsk"),
+    by_symbol("FI", "fi", "Finnish", "Suomi"),
+    by_symbol("L", "lt", "Lithuanian", "Lietuvių"),
+    by_symbol("LT", "lv", "Latvian", "Latviešu"),
+    by_symbol("ST", "et", "Estonian", "Eesti"),
+    by_symbol("IN", "id", "Indonesian", "Indonesia"),
+    by_symbol("TG", "tl", "Tagalog", "Tagalog"),
+    by_symbol("VT", "vi", "Vietnamese", "Việt"),
+    by_symbol("HI", "hi", "Hindi", "हिन्दी"),
+    by_symbol("SW", "sw", "Swahili", "Kiswahili"),
+    by_symbol("AF", "af", "Afrikaans", "Afrikaans"),
+    rtl(by_symbol("A", "ar", "Arabic", "العربية")),
+    rtl(by_symbol("Q", "he", "Hebrew", "עברית")),
+    rtl(by_symbol("PR", "fa", "Persian", "فارسی")),
+    rtl(by_symbol("UD", "ur", "Urdu", "اردو")),
+];
+
+/// Looks up a MEPS language index in the bundled table
+pub fn language_info(meps_index: i32) -> Option<LanguageInfo> {
+    LANGUAGES.iter().find(|language| language.meps_index == Some(meps_index)).copied()
+}
+
+/// Looks up a JW language symbol (`E`, `a`) in the bundled table
+pub fn language_info_for_symbol(symbol: &str) -> Option<LanguageInfo> {
+    LANGUAGES.iter().find(|language| language.symbol.eq_ignore_ascii_case(symbol)).copied()
+}
+
+/// Returns the JW language symbol (e.g. `E`, `S`) for a MEPS language index
+pub fn language_symbol(meps_index: i32) -> Option<&'static str> {
+    language_info(meps_index).map(|language| language.symbol)
+}
+
+/// Reading direction of a JW language symbol, `Ltr` unless it is known to be right-to-left
+pub fn language_direction(symbol: &str) -> TextDirection {
+    language_info_for_symbol(symbol).map_or(TextDirection::Ltr, |language| language.direction)
+}
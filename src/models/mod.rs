@@ -1,24 +1,489 @@
 use serde::{Serialize, Deserialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+mod classes;
+mod diff;
+mod languages;
+pub use classes::content_classes;
+pub use diff::{FieldChange, ManifestDiff};
+pub use languages::{language_direction, language_info, language_info_for_symbol, language_symbol, LanguageInfo};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Manifest {
     pub publication: String,
     pub year: u16,
     pub issue: String,
+    /// The issue tag broken down into year, month and week; `None` for publications without
+    /// issues (books, brochures)
+    #[serde(default)]
+    pub issue_info: Option<IssueInfo>,
+    /// First and last dates (`YYYY-MM-DD`) the dated texts cover, e.g. the weeks the study
+    /// articles are meant for. `None` for publications without dated texts.
+    #[serde(default)]
+    pub date_range: Option<(String, String)>,
+    /// Kind of publication, detected from the `Publication` table
+    #[serde(default)]
+    pub publication_type: PublicationType,
+    /// Version of the database schema (`Publication.SchemaVersion`), `None` when the database
+    /// does not record one
+    #[serde(default)]
+    pub schema_version: Option<String>,
     pub language: String,
+    /// English and native name of the language (`"French"`, `"Français"`), `None` when the
+    /// language is not in the bundled table
+    #[serde(default)]
+    pub language_name: Option<String>,
+    #[serde(default)]
+    pub language_native_name: Option<String>,
+    /// Reading direction of the language, for `dir="rtl"` in front ends
+    #[serde(default)]
+    pub direction: TextDirection,
     pub title: String,
     pub extracted_at: String,
     pub documents: Vec<Document>,
+    /// Documents that could not be decrypted when parsing with `skip_failed`
+    #[serde(default)]
+    pub errors: Vec<DocumentError>,
+    /// Navigation tree of the publication (sections, weeks, articles...), empty when the
+    /// database has no view items
+    #[serde(default)]
+    pub toc: Vec<TocEntry>,
+    /// Path of the cover image (`./assets/cover.<ext>`), `None` when the publication has no
+    /// cover or assets were not extracted
+    #[serde(default)]
+    pub cover: Option<String>,
+    /// Images referenced by the documents that are not in the archive, so their `<img>` would
+    /// be broken. Always empty when assets are not extracted.
+    #[serde(default)]
+    pub missing_assets: Vec<String>,
+}
+
+/// Reading direction of a publication's language
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TextDirection {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+/// An issue tag such as `20230100`, laid out as `YYYYMMWW`: a zero month or week means the
+/// issue is not that specific. Orders chronologically.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueInfo {
+    pub raw: String,
+    pub year: u16,
+    pub month: Option<u8>,
+    pub week: Option<u8>,
+}
+
+impl IssueInfo {
+    /// Parses an issue tag; `None` for the `0`/empty tag of undated publications or anything
+    /// that does not start with a year
+    pub fn parse(tag: &str) -> Option<Self> {
+        let raw = tag.trim();
+        if raw.len() < 4 || !raw.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let year: u16 = raw[..4].parse().ok()?;
+        if year == 0 {
+            return None;
+        }
+        let two_digits = |range: std::ops::Range<usize>| raw.get(range).and_then(|digits| digits.parse::<u8>().ok());
+        let month = two_digits(4..6).filter(|month| (1..=12).contains(month));
+        let week = two_digits(6..8).filter(|week| *week > 0);
+
+        Some(Self { raw: raw.to_string(), year, month, week })
+    }
+}
+
+impl PartialOrd for IssueInfo {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IssueInfo {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.year, self.month, self.week, &self.raw).cmp(&(other.year, other.month, other.week, &other.raw))
+    }
+}
+
+impl Manifest {
+    /// BCP-47 tag of the publication language (`en`, `pt-BR`) for `lang` attributes and EPUB
+    /// metadata; `und` when the language is not in the bundled table
+    pub fn language_code(&self) -> &'static str {
+        language_info_for_symbol(&self.language).map_or("und", |language| language.code)
+    }
+
+    /// Assets of every document without duplicates (by `file_name`), in order of first
+    /// appearance. Handy to plan a single download or copy of all the media.
+    pub fn all_assets(&self) -> Vec<&Asset> {
+        let mut seen = std::collections::HashSet::new();
+        self.documents
+            .iter()
+            .flat_map(|document| document.assets.iter())
+            .filter(|asset| seen.insert(asset.file_name.as_str()))
+            .collect()
+    }
+
+    /// References of every document grouped by `Reference::normalized_link`, most cited first
+    /// (ties keep the order of first appearance). The per-document lists are left as they are.
+    pub fn reference_index(&self) -> Vec<ReferenceIndexEntry> {
+        let mut entries: Vec<ReferenceIndexEntry> = Vec::new();
+        let mut positions = std::collections::HashMap::new();
+        for document in &self.documents {
+            for reference in &document.references {
+                let link = reference.normalized_link();
+                let position = *positions.entry(link.clone()).or_insert_with(|| {
+                    entries.push(ReferenceIndexEntry {
+                        link,
+                        r#type: reference.r#type,
+                        text: reference.text.clone(),
+                        bible_citation: reference.bible_citation.clone(),
+                        count: 0,
+                        document_ids: Vec::new(),
+                    });
+                    entries.len() - 1
+                });
+
+                let entry = &mut entries[position];
+                entry.count += 1;
+                if !entry.document_ids.contains(&document.id) {
+                    entry.document_ids.push(document.id);
+                }
+            }
+        }
+
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.count));
+        entries
+    }
+}
+
+/// A reference target aggregated over the whole publication by `Manifest::reference_index`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferenceIndexEntry {
+    /// Normalized link shared by every occurrence
+    pub link: String,
+    pub r#type: ReferenceType,
+    /// Text of the first occurrence; the others may word it differently
+    pub text: String,
+    pub bible_citation: Option<BibleCitation>,
+    /// Number of occurrences across all documents
+    pub count: usize,
+    /// Documents holding the reference, in manifest order
+    pub document_ids: Vec<u32>,
+}
+
+/// Kind of publication, which (with the symbol) decides the document classes read by default
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PublicationType {
+    /// The Watchtower and Awake!
+    Magazine,
+    MeetingWorkbook,
+    Book,
+    Brochure,
+    Bible,
+    #[default]
+    Other,
+}
+
+impl PublicationType {
+    /// Detects the type from the `PublicationType` text (e.g. "Meeting Workbook") or, when it
+    /// is missing or unknown, the `PublicationCategorySymbol` and finally the symbol itself
+    pub fn detect(publication_type: Option<&str>, category_symbol: Option<&str>, symbol: &str) -> Self {
+        if let Some(kind) = publication_type.and_then(Self::from_type_name) {
+            return kind;
+        }
+        if let Some(kind) = category_symbol.and_then(Self::from_category_symbol) {
+            return kind;
+        }
+        if symbol.to_lowercase().starts_with("mwb") {
+            return PublicationType::MeetingWorkbook;
+        }
+        Self::from_category_symbol(symbol).unwrap_or(PublicationType::Other)
+    }
+
+    fn from_type_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        // "Workbook" must be checked before "Book"
+        if name.contains("workbook") {
+            Some(PublicationType::MeetingWorkbook)
+        } else if name.contains("watchtower") || name.contains("awake") || name.contains("magazine") {
+            Some(PublicationType::Magazine)
+        } else if name.contains("brochure") {
+            Some(PublicationType::Brochure)
+        } else if name.contains("bible") {
+            Some(PublicationType::Bible)
+        } else if name.contains("book") {
+            Some(PublicationType::Book)
+        } else {
+            None
+        }
+    }
+
+    fn from_category_symbol(category: &str) -> Option<Self> {
+        match category.trim().to_lowercase().as_str() {
+            "mwb" => Some(PublicationType::MeetingWorkbook),
+            "w" | "ws" | "wp" | "g" => Some(PublicationType::Magazine),
+            "bk" => Some(PublicationType::Book),
+            "brch" => Some(PublicationType::Brochure),
+            "bi" => Some(PublicationType::Bible),
+            _ => None,
+        }
+    }
+}
+
+/// A node of the publication's table of contents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TocEntry {
+    pub title: String,
+    /// MepsDocumentId opened by this entry, `None` for pure grouping nodes
+    pub doc_id: Option<u32>,
+    pub children: Vec<TocEntry>,
+}
+
+/// Id, title and class of a document, read without decrypting it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentSummary {
+    pub id: u32,
+    pub title: String,
+    pub class: i32,
+}
+
+/// Raw view of the database for bug reports, from `dump_database`: nothing is decrypted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseDump {
+    /// Every column of the first `Publication` row, as stored; blobs are only described
+    pub publication: serde_json::Map<String, serde_json::Value>,
+    /// Rows in the `Document` table
+    pub document_count: usize,
+    /// The first rows of `Document`, in `DocumentId` order
+    pub documents: Vec<DocumentRowDump>,
+}
+
+/// A `Document` row of `DatabaseDump`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentRowDump {
+    pub id: u32,
+    pub title: Option<String>,
+    /// Bytes of the still encrypted `Content`, `None` when it is NULL
+    pub content_length: Option<usize>,
+}
+
+/// Result of each integrity check of `validate_jwpub`. A check that depends on an earlier
+/// failed one is `false` too; `problems` says what went wrong, in order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport {
+    /// The file is a readable ZIP (and not an EPUB)
+    pub is_zip: bool,
+    /// It holds an inner `contents` archive that opens as a ZIP
+    pub has_contents: bool,
+    /// The inner archive holds a SQLite `.db` that opens
+    pub has_database: bool,
+    /// The `Publication` table exists and has exactly one row
+    pub has_publication: bool,
+    /// A sample document decrypts with the derived keys
+    pub sample_decrypts: bool,
+    /// Name of the database inside `contents`
+    pub database_name: Option<String>,
+    /// Document used for the decryption check; `None` when there is none with content
+    pub sample_document_id: Option<u32>,
+    pub problems: Vec<String>,
+}
+
+impl ValidationReport {
+    /// Whether every check passed
+    pub fn is_valid(&self) -> bool {
+        self.is_zip && self.has_contents && self.has_database && self.has_publication && self.sample_decrypts
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocumentError {
+    pub id: u32,
+    pub reason: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Document {
     pub id: u32,
+    #[serde(default)]
+    pub class: i32,
+    /// `Title`, falling back to `feature_title` and then `context_title` when it is empty
     pub title: String,
+    /// `ContextTitle` of the database, e.g. the section of a meeting workbook part
+    #[serde(default)]
+    pub context_title: Option<String>,
+    /// `FeatureTitle` of the database
+    #[serde(default)]
+    pub feature_title: Option<String>,
     pub html: String,
+    /// Decrypted HTML exactly as stored, before image paths are rewritten
+    #[serde(default)]
+    pub html_raw: String,
     pub references: Vec<Reference>,
     pub assets: Vec<Asset>,
-    pub paragraphs: Vec<String>,
+    /// Manifests written before paragraphs had ids list them as plain strings
+    #[serde(deserialize_with = "deserialize_paragraphs")]
+    pub paragraphs: Vec<Paragraph>,
+    #[serde(default)]
+    pub footnotes: Vec<Footnote>,
+    #[serde(default)]
+    pub headings: Vec<Heading>,
+    /// Study questions, empty for publications without them
+    #[serde(default)]
+    pub questions: Vec<StudyQuestion>,
+    /// Headings, paragraphs, captions and quotes interleaved in reading order
+    #[serde(default)]
+    pub content_blocks: Vec<ContentBlock>,
+    /// Tag-free text for full-text indexing; blocks are separated by `\n\n`
+    #[serde(default)]
+    pub plain_text: String,
+    /// Hex SHA-256 of the decrypted HTML before any rewriting, to detect changed documents
+    /// between downloads. Empty in manifests written before it existed.
+    #[serde(default)]
+    pub content_hash: String,
+}
+
+fn deserialize_paragraphs<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<Paragraph>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StoredParagraph {
+        Text(String),
+        Paragraph(Paragraph),
+    }
+
+    let stored = Vec::<StoredParagraph>::deserialize(deserializer)?;
+    Ok(stored
+        .into_iter()
+        .map(|paragraph| match paragraph {
+            StoredParagraph::Text(text) => Paragraph { id: None, data_pid: None, text },
+            StoredParagraph::Paragraph(paragraph) => paragraph,
+        })
+        .collect())
+}
+
+impl Document {
+    /// Plain text of every paragraph, as the manifest used to expose them
+    pub fn paragraph_texts(&self) -> Vec<String> {
+        self.paragraphs.iter().map(|p| p.text.clone()).collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Paragraph {
+    /// `id` attribute of the `p` element
+    pub id: Option<String>,
+    /// `data-pid` attribute, used to anchor study questions
+    pub data_pid: Option<String>,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Heading {
+    /// 1 to 6, from the `h1`-`h6` tag
+    pub level: u8,
+    pub text: String,
+    pub id: Option<String>,
+    /// Number of paragraphs preceding the heading, to interleave it with `Document::paragraphs`
+    pub paragraph_offset: usize,
+}
+
+/// A unit of text of a document in reading order, for TTS or linear rendering
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ContentBlock {
+    Heading { level: u8, text: String, #[serde(default)] region: ContentRegion },
+    Paragraph { text: String, #[serde(default)] region: ContentRegion },
+    /// Text of a `figcaption`
+    Caption { text: String, #[serde(default)] region: ContentRegion },
+    /// Text of a `blockquote`
+    Quote { text: String, #[serde(default)] region: ContentRegion },
+    /// A `ul` (`ordered: false`) or `ol` list
+    List { ordered: bool, items: Vec<ListItem>, #[serde(default)] region: ContentRegion },
+}
+
+impl ContentBlock {
+    /// Region of the document the block was found in
+    pub fn region(&self) -> ContentRegion {
+        match self {
+            ContentBlock::Heading { region, .. }
+            | ContentBlock::Paragraph { region, .. }
+            | ContentBlock::Caption { region, .. }
+            | ContentBlock::Quote { region, .. }
+            | ContentBlock::List { region, .. } => *region,
+        }
+    }
+}
+
+/// Part of the document a `ContentBlock` belongs to, from the CSS classes of its containers
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ContentRegion {
+    /// The running text of the article
+    #[default]
+    Body,
+    /// The `<header>`: title, theme scripture, song
+    Header,
+    /// Boxes set apart from the body (`boxContent`, `boxSupplement`, `<aside>`)
+    Box,
+    /// Teaching and review boxes (`blockTeach`, `*review*`), e.g. "What Can You Learn?"
+    Review,
+    /// Footnote bodies (`footnote`, `footnotes`)
+    Footnote,
+}
+
+/// An `li` of a `ContentBlock::List`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ListItem {
+    /// Text of the item without its nested lists
+    pub text: String,
+    /// Lists nested inside the item, in document order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lists: Vec<List>,
+}
+
+/// A list nested inside a `ListItem`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct List {
+    pub ordered: bool,
+    pub items: Vec<ListItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StudyQuestion {
+    /// Question text without its leading paragraph numbers
+    pub text: String,
+    /// Paragraph numbers the question covers, ranges expanded (`"3-5."` gives 3, 4 and 5)
+    pub paragraph_refs: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Footnote {
+    pub id: String,
+    /// Text of the footnote, without the footnotes nested in it
+    pub text: String,
+    /// Bible verses cited by the footnote's own links
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bible_citations: Vec<BibleCitation>,
+    /// Index in `Document::paragraphs` of the paragraph holding the footnote marker
+    pub paragraph_index: Option<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -27,31 +492,294 @@ pub struct Reference {
     pub r#type: ReferenceType,
     pub link: String,
     pub text: String,
+    /// Parsed target of a `bible://` link, `None` when the link format is not recognized
+    pub bible_citation: Option<BibleCitation>,
+    /// Parsed target of a `jwpub://` link, `None` when the link format is not recognized
+    pub publication_citation: Option<PublicationCitation>,
+    /// Song number of an audio link, from the link itself or its text ("Song 123")
+    #[serde(default)]
+    pub song_number: Option<u32>,
+    /// Index in `Document::paragraphs` of the paragraph holding the link, `None` outside
+    /// paragraphs (e.g. in headings)
+    pub paragraph_index: Option<usize>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+impl Reference {
+    /// Link identifying the target regardless of how it is written: Bible links become
+    /// `bible://v/<book>/<chapter>/<verse>[-<chapter>/<verse>]`, so compact verse ids and paths
+    /// to the same verses are equal. Other links are only trimmed.
+    pub fn normalized_link(&self) -> String {
+        match &self.bible_citation {
+            Some(citation) => citation.to_link(),
+            None => self.link.trim().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BibleCitation {
+    pub book: u16,
+    pub chapter_start: u16,
+    pub verse_start: u16,
+    pub chapter_end: u16,
+    pub verse_end: u16,
+}
+
+impl BibleCitation {
+    /// Parses the Bible link formats found in JWPUB HTML:
+    /// - `bible://v/<book>/<chapter>/<verse>` with an optional `-<verse>` or `-<chapter>/<verse>` end
+    /// - compact `BBCCCVVV` verse ids (e.g. `bible://NWTR/E/43003016-43003018`)
+    pub fn parse(link: &str) -> Option<Self> {
+        let rest = link.strip_prefix("bible://")?;
+        let (start, end) = match rest.split_once('-') {
+            Some((start, end)) => (start, Some(end)),
+            None => (rest, None),
+        };
+
+        let segments: Vec<&str> = start.split('/').filter(|s| !s.is_empty()).collect();
+        let last = *segments.last()?;
+
+        let (book, chapter_start, verse_start) = if last.len() >= 7 {
+            Self::parse_compact(last)?
+        } else if segments.len() >= 3 {
+            let n = segments.len();
+            (
+                segments[n - 3].parse().ok()?,
+                segments[n - 2].parse().ok()?,
+                segments[n - 1].parse().ok()?,
+            )
+        } else {
+            return None;
+        };
+
+        let (chapter_end, verse_end) = match end {
+            None => (chapter_start, verse_start),
+            Some(end) => {
+                let end_segments: Vec<&str> = end.split('/').filter(|s| !s.is_empty()).collect();
+                match end_segments.as_slice() {
+                    [compact] if compact.len() >= 7 => {
+                        let (end_book, chapter, verse) = Self::parse_compact(compact)?;
+                        if end_book != book {
+                            return None;
+                        }
+                        (chapter, verse)
+                    }
+                    [verse] => (chapter_start, verse.parse().ok()?),
+                    [chapter, verse] => (chapter.parse().ok()?, verse.parse().ok()?),
+                    _ => return None,
+                }
+            }
+        };
+
+        Some(Self { book, chapter_start, verse_start, chapter_end, verse_end })
+    }
+
+    /// Canonical `bible://v/...` link of the citation, which `parse` reads back
+    pub fn to_link(&self) -> String {
+        let start = format!("bible://v/{}/{}/{}", self.book, self.chapter_start, self.verse_start);
+        if (self.chapter_end, self.verse_end) == (self.chapter_start, self.verse_start) {
+            start
+        } else {
+            format!("{}-{}/{}", start, self.chapter_end, self.verse_end)
+        }
+    }
+
+    /// Decodes a `BBCCCVVV` verse id into (book, chapter, verse)
+    fn parse_compact(id: &str) -> Option<(u16, u16, u16)> {
+        let n: u32 = id.parse().ok()?;
+        let book = (n / 1_000_000) as u16;
+        let chapter = ((n / 1000) % 1000) as u16;
+        let verse = (n % 1000) as u16;
+        if book == 0 || chapter == 0 {
+            return None;
+        }
+        Some((book, chapter, verse))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicationCitation {
+    /// Publication symbol, only present when the link names one (`jwpub://p/w/E:...`)
+    pub pub_symbol: Option<String>,
+    /// Language symbol prefixing the document id (`E` in `E:1102023201`)
+    pub language: Option<String>,
+    /// Target MepsDocumentId
+    pub doc_id: u32,
+    /// First paragraph (`data-pid`) of the target, when the link points inside the document
+    pub paragraph: Option<u32>,
+    /// Last paragraph of a range; equals `paragraph` for a single one
+    pub paragraph_end: Option<u32>,
+}
+
+impl PublicationCitation {
+    /// Parses inter-publication links such as `jwpub://p/E:1102023201/2-3`:
+    /// `jwpub://p/[<symbol>/]<lang>:<docId>[/<paragraph>[-<paragraph>]]`. The language prefix is optional.
+    pub fn parse(link: &str) -> Option<Self> {
+        let rest = link.strip_prefix("jwpub://p/")?;
+        let segments: Vec<&str> = rest.split('/').filter(|s| !s.is_empty()).collect();
+
+        let doc_index = segments.iter().position(|segment| Self::parse_doc_id(segment).is_some())?;
+        let (language, doc_id) = Self::parse_doc_id(segments[doc_index])?;
+        let pub_symbol = match doc_index {
+            0 => None,
+            1 => Some(segments[0].to_string()),
+            _ => return None,
+        };
+
+        let (paragraph, paragraph_end) = match &segments[doc_index + 1..] {
+            [] => (None, None),
+            [range] => {
+                let (start, end) = range.split_once('-').unwrap_or((range, range));
+                (Some(start.parse().ok()?), Some(end.parse().ok()?))
+            }
+            _ => return None,
+        };
+
+        Some(Self { pub_symbol, language, doc_id, paragraph, paragraph_end })
+    }
+
+    /// Splits `E:1102023201` (or a bare `1102023201`) into language and document id
+    fn parse_doc_id(segment: &str) -> Option<(Option<String>, u32)> {
+        match segment.split_once(':') {
+            Some((language, id)) if !language.is_empty() => Some((Some(language.to_string()), id.parse().ok()?)),
+            Some(_) => None,
+            None => Some((None, segment.parse().ok()?)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum ReferenceType {
     Bible,
     Publication,
     Video,
+    /// `webpubaudio://` links, e.g. the songs of the meeting workbook
+    Audio,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Asset {
     pub file_name: String,
+    /// `alt` attribute, or the caption when `alt` is empty
     pub alt_text: String,
+    /// Text of the `figcaption` of the `figure` holding the image
+    pub caption: Option<String>,
     pub r#type: AssetType,
+    /// Pixel dimensions and MIME type read from the image header during extraction.
+    /// `None` when assets are not extracted or when the image cannot be probed.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub mime: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum AssetType {
     Image,
+    /// Only in manifests written by earlier versions; videos are now `Reference`s
     Video,
 }
 
+/// Full result of a parse: the manifest plus every asset physically written to disk
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseOutput {
+    pub manifest: Manifest,
+    pub extracted_assets: Vec<ExtractedAsset>,
+    pub stats: ParseStats,
+}
+
+/// Counters and timings of a parse, to spot publications that come out suspiciously empty
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseStats {
+    pub documents: usize,
+    /// Documents skipped with `skip_failed`
+    pub failed_documents: usize,
+    pub bible_references: usize,
+    pub publication_references: usize,
+    pub video_references: usize,
+    #[serde(default)]
+    pub audio_references: usize,
+    pub image_assets: usize,
+    /// Size of the decrypted HTML of every document
+    pub decrypted_bytes: u64,
+    /// Document classes that were extracted
+    pub class_ids: Vec<i32>,
+    pub timings: StageTimings,
+}
+
+impl ParseStats {
+    /// Counts documents, references and assets of a manifest; timings are left at zero
+    pub fn count(manifest: &Manifest) -> Self {
+        let mut stats = ParseStats {
+            documents: manifest.documents.len(),
+            failed_documents: manifest.errors.len(),
+            ..Default::default()
+        };
+
+        for document in &manifest.documents {
+            stats.decrypted_bytes += document.html_raw.len() as u64;
+            for reference in &document.references {
+                match reference.r#type {
+                    ReferenceType::Bible => stats.bible_references += 1,
+                    ReferenceType::Publication => stats.publication_references += 1,
+                    ReferenceType::Video => stats.video_references += 1,
+                    ReferenceType::Audio => stats.audio_references += 1,
+                }
+            }
+            stats.image_assets += document.assets.iter().filter(|asset| asset.r#type == AssetType::Image).count();
+        }
+
+        stats
+    }
+}
+
+/// Wall-clock time spent in each stage of the pipeline
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageTimings {
+    /// Opening the archives and database and deriving the keys
+    pub unzipping: Duration,
+    /// Decrypting and parsing the documents (the two overlap across threads)
+    pub documents: Duration,
+    pub extracting_assets: Duration,
+    pub total: Duration,
+}
+
+/// A file copied out of the inner 'contents' archive
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractedAsset {
+    /// Original path of the entry inside the zip
+    pub source_path: String,
+    /// Absolute path of the written file
+    pub path: PathBuf,
+    pub size: u64,
+    pub mime_type: String,
+}
+
+/// Pipeline stage reported through `ParseOptions::on_progress`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseStage {
+    Unzipping,
+    Decrypting,
+    ParsingHtml,
+    ExtractingAssets,
+}
+
+/// Progress event. `current`/`total` count documents (or archive entries while extracting assets).
+#[derive(Debug, Clone, Copy)]
+pub struct ParseProgress {
+    pub stage: ParseStage,
+    pub current: usize,
+    pub total: usize,
+}
+
 // Internal struct for DB mapping (not exposed in JSON necessarily)
 #[derive(Debug)]
 pub struct DbPublication {
@@ -59,4 +787,116 @@ pub struct DbPublication {
     pub symbol: String,
     pub year: i32,
     pub issue_tag_number: String,
+    pub title: Option<String>,
+    /// `ShortTitle`, or `UndatedTitle` on schemas without it
+    pub short_title: Option<String>,
+    /// `PublicationType` text, e.g. "Watchtower" or "Meeting Workbook"
+    pub publication_type: Option<String>,
+    pub category_symbol: Option<String>,
+}
+
+impl DbPublication {
+    /// Best display title: `Title`, then the short title, then the symbol
+    pub fn display_title(&self) -> String {
+        [&self.title, &self.short_title]
+            .into_iter()
+            .flatten()
+            .map(|title| title.trim())
+            .find(|title| !title.is_empty())
+            .unwrap_or(&self.symbol)
+            .to_string()
+    }
+
+    /// Key derivation input: `mepsLang_symbol_year_issue`
+    pub fn pub_card(&self) -> String {
+        format!("{}_{}_{}_{}", self.meps_language_index, self.symbol, self.year, self.issue_tag_number)
+    }
+
+    /// Overrides the fields the archive manifest provides
+    pub fn apply_manifest(&mut self, manifest: &ArchiveManifestPublication) {
+        if let Some(symbol) = manifest.symbol.as_ref().filter(|symbol| !symbol.is_empty()) {
+            self.symbol = symbol.clone();
+        }
+        if let Some(year) = manifest.year {
+            self.year = year;
+        }
+        match &manifest.issue_tag_number {
+            Some(serde_json::Value::Number(number)) => self.issue_tag_number = number.to_string(),
+            Some(serde_json::Value::String(issue)) if !issue.is_empty() => self.issue_tag_number = issue.clone(),
+            _ => {}
+        }
+        if let Some(language) = manifest.language {
+            self.meps_language_index = language;
+        }
+    }
+
+    pub fn publication_type(&self) -> PublicationType {
+        PublicationType::detect(self.publication_type.as_deref(), self.category_symbol.as_deref(), &self.symbol)
+    }
+}
+
+/// `manifest.json` shipped in the JWPUB (inside the contents archive or next to it)
+#[derive(Debug, Default, Deserialize)]
+pub struct ArchiveManifest {
+    #[serde(default)]
+    pub publication: Option<ArchiveManifestPublication>,
+}
+
+/// Publication metadata of `manifest.json`, authoritative over the database row when present
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveManifestPublication {
+    /// Name of the database inside the contents archive
+    pub file_name: Option<String>,
+    pub symbol: Option<String>,
+    pub year: Option<i32>,
+    /// Issue tag, stored either as a number or a string
+    #[serde(alias = "issueId")]
+    pub issue_tag_number: Option<serde_json::Value>,
+    /// MEPS language index
+    pub language: Option<i32>,
+}
+
+// Row of the Multimedia table linked to a document
+#[derive(Debug, Clone)]
+pub struct MultimediaRow {
+    pub file_path: String,
+    pub caption: Option<String>,
+    pub alt_text: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub mime_type: Option<String>,
+}
+
+/// Row of the Document table, content still encrypted
+#[derive(Debug, Clone)]
+pub struct DocumentRow {
+    pub id: u32,
+    pub title: String,
+    /// `ContextTitle`, e.g. the section a meeting workbook part belongs to
+    pub context_title: Option<String>,
+    /// `FeatureTitle`, the heading shown for featured articles and parts
+    pub feature_title: Option<String>,
+    pub content: Vec<u8>,
+}
+
+impl DocumentRow {
+    /// `Title`, or the feature/context title when it is empty
+    pub fn display_title(&self) -> String {
+        [Some(&self.title), self.feature_title.as_ref(), self.context_title.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|title| title.trim())
+            .find(|title| !title.is_empty())
+            .unwrap_or_default()
+            .to_string()
+    }
+}
+
+// Row of the BibleVerse table (study Bibles), still encrypted
+#[derive(Debug, Clone)]
+pub struct VerseRow {
+    pub verse_number: u32,
+    pub label: Option<String>,
+    pub content: Vec<u8>,
 }
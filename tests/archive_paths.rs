@@ -0,0 +1,47 @@
+#![cfg(feature = "native")]
+
+mod common;
+
+use common::JwpubBuilder;
+use jw_parser::assets::{AssetSink, FsAssetSink};
+use jw_parser::{parse_jwpub, ParseOptions};
+
+const JPEG: &[u8] = &[0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10, b'J', b'F', b'I', b'F'];
+
+#[test]
+fn traversal_entries_are_not_extracted() {
+    let root = tempfile::tempdir().unwrap();
+    let output = root.path().join("out");
+    let jwpub = JwpubBuilder::new()
+        .document(1102023201, "Article", r#"<p id="p1"><img src="jwpub-media://safe.jpg"></p>"#)
+        .entry("safe.jpg", JPEG)
+        .entry("../evil.jpg", JPEG)
+        .entry("sub/../../escape.jpg", JPEG)
+        .entry("/absolute.jpg", JPEG)
+        .write_to(root.path());
+
+    let manifest = parse_jwpub(jwpub.as_path(), output.as_path(), &ParseOptions::default()).unwrap();
+    assert!(manifest.missing_assets.is_empty());
+
+    let mut written: Vec<_> = walkdir::WalkDir::new(root.path())
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.path().strip_prefix(root.path()).unwrap().to_string_lossy().replace('\\', "/"))
+        .collect();
+    written.sort();
+    assert_eq!(written, ["out/assets/safe.jpg", "w_E_202301.jwpub"]);
+}
+
+#[test]
+fn file_sink_refuses_names_outside_its_directory() {
+    let root = tempfile::tempdir().unwrap();
+    let mut sink = FsAssetSink::new(root.path().join("assets")).unwrap();
+
+    for name in ["../evil.jpg", "a/../../evil.jpg", "/absolute.jpg", ""] {
+        assert!(sink.write_asset(name, &mut &JPEG[..]).is_err(), "{:?} was written", name);
+    }
+    sink.write_asset("ab/cd/ok.jpg", &mut &JPEG[..]).unwrap();
+    assert!(root.path().join("assets/ab/cd/ok.jpg").is_file());
+    assert!(!root.path().join("evil.jpg").exists());
+}
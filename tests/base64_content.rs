@@ -0,0 +1,17 @@
+mod common;
+
+use common::JwpubBuilder;
+use jw_parser::parse_jwpub_bytes;
+
+#[test]
+fn reads_content_stored_as_base64_text() {
+    let data = JwpubBuilder::new()
+        .document(1102023201, "First", "<p id=\"p1\">Stored as base64</p>")
+        .document(1102023202, "Second", "<p id=\"p1\">Also base64</p>")
+        .base64_content()
+        .build();
+
+    let (manifest, _) = parse_jwpub_bytes(&data).unwrap();
+    let texts: Vec<_> = manifest.documents.iter().map(|document| document.paragraphs[0].text.as_str()).collect();
+    assert_eq!(texts, ["Stored as base64", "Also base64"]);
+}
@@ -0,0 +1,169 @@
+//! In-memory JWPUB fixtures for the integration tests
+#![allow(dead_code)]
+
+use base64::{engine::general_purpose, Engine as _};
+use jw_parser::crypto::CryptoService;
+use rusqlite::{Connection, DatabaseName};
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+
+/// Document class the crate extracts from a Watchtower
+const ARTICLE_CLASS: i32 = 40;
+
+/// Builds a small but complete JWPUB: outer zip, `contents` zip, SQLite database with
+/// encrypted documents, plus any extra entries of the `contents` archive (e.g. images)
+/// Defaults to the English Watchtower of January 2023.
+pub struct JwpubBuilder {
+    meps_language: i32,
+    language_symbol: String,
+    documents: Vec<(u32, String, String)>,
+    corrupt_documents: Vec<(u32, String)>,
+    entries: Vec<(String, Vec<u8>)>,
+    db_images: Vec<(String, Vec<u8>)>,
+    base64_content: bool,
+}
+
+impl Default for JwpubBuilder {
+    fn default() -> Self {
+        Self {
+            meps_language: 0,
+            language_symbol: "E".to_string(),
+            documents: Vec::new(),
+            corrupt_documents: Vec::new(),
+            entries: Vec::new(),
+            db_images: Vec::new(),
+            base64_content: false,
+        }
+    }
+}
+
+impl JwpubBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publishes in another language: `meps_language` goes to the `Publication` table and
+    /// `symbol` only to the file names, as no `Language` table is written
+    pub fn language(mut self, meps_language: i32, symbol: &str) -> Self {
+        self.meps_language = meps_language;
+        self.language_symbol = symbol.to_string();
+        self
+    }
+
+    /// Adds an article with this MepsDocumentId, title and HTML
+    pub fn document(mut self, id: u32, title: &str, html: &str) -> Self {
+        self.documents.push((id, title.to_string(), html.to_string()));
+        self
+    }
+
+    /// Adds an article whose content does not decrypt. Corrupt articles come before the others.
+    pub fn corrupt_document(mut self, id: u32, title: &str) -> Self {
+        self.corrupt_documents.push((id, title.to_string()));
+        self
+    }
+
+    /// Adds an entry to the `contents` archive; the name is stored as given
+    pub fn entry(mut self, name: &str, bytes: &[u8]) -> Self {
+        self.entries.push((name.to_string(), bytes.to_vec()));
+        self
+    }
+
+    /// Adds an image stored encrypted in the `Image` table instead of the archive
+    pub fn db_image(mut self, name: &str, bytes: &[u8]) -> Self {
+        self.db_images.push((name.to_string(), bytes.to_vec()));
+        self
+    }
+
+    /// Stores `Document.Content` and `Image.Data` as base64 TEXT instead of a BLOB
+    pub fn base64_content(mut self) -> Self {
+        self.base64_content = true;
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let options = FileOptions::default();
+
+        let mut contents = ZipWriter::new(Cursor::new(Vec::new()));
+        contents.start_file(format!("{}.db", self.file_stem()), options).unwrap();
+        contents.write_all(&self.database()).unwrap();
+        for (name, bytes) in &self.entries {
+            contents.start_file(name.as_str(), options).unwrap();
+            contents.write_all(bytes).unwrap();
+        }
+        let contents = contents.finish().unwrap().into_inner();
+
+        let mut jwpub = ZipWriter::new(Cursor::new(Vec::new()));
+        jwpub.start_file("manifest.json", options).unwrap();
+        jwpub.write_all(format!(r#"{{"name":"{}.jwpub"}}"#, self.file_stem()).as_bytes()).unwrap();
+        jwpub.start_file("contents", options).unwrap();
+        jwpub.write_all(&contents).unwrap();
+        jwpub.finish().unwrap().into_inner()
+    }
+
+    /// Writes the publication as `<dir>/w_<language>_202301.jwpub`
+    pub fn write_to(&self, dir: &Path) -> PathBuf {
+        let path = dir.join(format!("{}.jwpub", self.file_stem()));
+        std::fs::write(&path, self.build()).unwrap();
+        path
+    }
+
+    fn file_stem(&self) -> String {
+        format!("w_{}_202301", self.language_symbol)
+    }
+
+    fn database(&self) -> Vec<u8> {
+        let crypto = CryptoService::new().unwrap();
+        let (key, iv) = crypto.derive_keys(&format!("{}_w_2023_20230100", self.meps_language));
+
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE Publication (PublicationId INTEGER, MepsLanguageIndex INTEGER, Symbol TEXT, Year INTEGER, \
+                 IssueTagNumber TEXT, Title TEXT, ShortTitle TEXT, UndatedTitle TEXT, PublicationType TEXT, \
+                 PublicationCategorySymbol TEXT);
+             CREATE TABLE Document (DocumentId INTEGER, MepsDocumentId INTEGER, Class INTEGER, Title TEXT, Content BLOB);
+             CREATE TABLE Image (FilePath TEXT, Data BLOB);",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO Publication VALUES (1, ?1, 'w', 2023, '20230100', 'The Watchtower (Study)—2023', \
+                 'Watchtower', 'The Watchtower', 'Watchtower', 'w')",
+            [self.meps_language],
+        )
+        .unwrap();
+
+        for (index, (id, title)) in self.corrupt_documents.iter().enumerate() {
+            conn.execute(
+                "INSERT INTO Document VALUES (?1, ?2, ?3, ?4, ?5)",
+                (index + 1, id, ARTICLE_CLASS, title, vec![0xA5u8; 32]),
+            )
+            .unwrap();
+        }
+
+        let offset = self.corrupt_documents.len();
+        for (index, (id, title, html)) in self.documents.iter().enumerate() {
+            let index = offset + index;
+            let content = crypto.encrypt_and_deflate(html, &key, &iv).unwrap();
+            let mut stmt = conn.prepare("INSERT INTO Document VALUES (?1, ?2, ?3, ?4, ?5)").unwrap();
+            if self.base64_content {
+                stmt.execute((index + 1, id, ARTICLE_CLASS, title, general_purpose::STANDARD.encode(content))).unwrap();
+            } else {
+                stmt.execute((index + 1, id, ARTICLE_CLASS, title, content)).unwrap();
+            }
+        }
+
+        for (name, bytes) in &self.db_images {
+            let data = crypto.encrypt(bytes, &key, &iv).unwrap();
+            let mut stmt = conn.prepare("INSERT INTO Image VALUES (?1, ?2)").unwrap();
+            if self.base64_content {
+                stmt.execute((name, general_purpose::STANDARD.encode(data))).unwrap();
+            } else {
+                stmt.execute((name, data)).unwrap();
+            }
+        }
+
+        conn.serialize(DatabaseName::Main).unwrap().to_vec()
+    }
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use base64::{engine::general_purpose, Engine as _};
+use common::JwpubBuilder;
+use jw_parser::parse_jwpub_bytes;
+
+const JPEG: &[u8] = &[0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10, b'J', b'F', b'I', b'F'];
+/// 1×1 transparent PNG
+const PNG_BASE64: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+fn png() -> Vec<u8> {
+    general_purpose::STANDARD.decode(PNG_BASE64).unwrap()
+}
+
+#[test]
+fn database_images_under_another_extension_are_renamed_in_the_documents() {
+    let data = JwpubBuilder::new()
+        .document(
+            1102023201,
+            "Article",
+            r#"<p id="p1"><img src="jwpub-media://chart.jpg"><img src="jwpub-media://photo.jpg"></p>"#,
+        )
+        .entry("photo.jpg", JPEG)
+        .db_image("chart.jpg", &png())
+        .db_image("photo.jpg", JPEG)
+        .build();
+
+    let (manifest, assets) = parse_jwpub_bytes(&data).unwrap();
+    let mut written: Vec<_> = assets.iter().map(|(name, _)| name.as_str()).collect();
+    written.sort();
+    assert_eq!(written, ["chart.png", "photo-2.jpg", "photo.jpg"]);
+
+    let document = &manifest.documents[0];
+    assert!(document.html.contains("./assets/chart.png"), "{}", document.html);
+    assert!(document.html.contains("./assets/photo.jpg"), "{}", document.html);
+    let chart = document.assets.iter().find(|asset| asset.file_name == "chart.png").unwrap();
+    assert_eq!((chart.width, chart.height, chart.mime.as_deref()), (Some(1), Some(1), Some("image/png")));
+    assert!(manifest.missing_assets.is_empty());
+}
+
+#[test]
+fn reads_database_images_stored_as_base64_text() {
+    let data = JwpubBuilder::new()
+        .document(1102023201, "Article", r#"<p id="p1"><img src="jwpub-media://chart.png"></p>"#)
+        .db_image("chart.png", &png())
+        .base64_content()
+        .build();
+
+    let (manifest, assets) = parse_jwpub_bytes(&data).unwrap();
+    assert_eq!(assets, [("chart.png".to_string(), png())]);
+    assert!(manifest.missing_assets.is_empty());
+}
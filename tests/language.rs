@@ -0,0 +1,49 @@
+mod common;
+
+use common::JwpubBuilder;
+use jw_parser::export::combined_html;
+use jw_parser::models::{language_direction, language_info, language_info_for_symbol, TextDirection};
+use jw_parser::parse_jwpub_bytes;
+
+#[test]
+fn bundled_languages_resolve_their_names() {
+    let data = JwpubBuilder::new().language(3, "F").document(1102023201, "Article", "<p>Bonjour</p>").build();
+
+    let (manifest, _) = parse_jwpub_bytes(&data).unwrap();
+    assert_eq!(manifest.language, "F");
+    assert_eq!(manifest.language_native_name.as_deref(), Some("Français"));
+    assert_eq!(manifest.language_code(), "fr");
+    assert_eq!(manifest.direction, TextDirection::Ltr);
+}
+
+#[test]
+fn unknown_languages_have_an_undetermined_code() {
+    let data = JwpubBuilder::new().language(999, "ZZZ").document(1102023201, "Article", "<p>Text</p>").build();
+
+    let (manifest, _) = parse_jwpub_bytes(&data).unwrap();
+    assert_eq!(manifest.language, "ZZZ");
+    assert_eq!(manifest.language_code(), "und");
+}
+
+#[test]
+fn right_to_left_languages_without_a_language_table() {
+    // The MEPS index is not in the bundled table, so the symbol comes from the file names
+    let data = JwpubBuilder::new().language(39, "A").document(1102023201, "Article", "<p>مرحبا</p>").build();
+
+    let (manifest, _) = parse_jwpub_bytes(&data).unwrap();
+    assert_eq!(manifest.language, "A");
+    assert_eq!(manifest.language_name.as_deref(), Some("Arabic"));
+    assert_eq!(manifest.direction, TextDirection::Rtl);
+    assert!(combined_html(&manifest, "assets").contains(r#"<html lang="ar" dir="rtl">"#));
+}
+
+#[test]
+fn bundled_table_covers_right_to_left_languages() {
+    let hebrew = language_info_for_symbol("q").unwrap();
+    assert_eq!((hebrew.symbol, hebrew.code, hebrew.direction), ("Q", "he", TextDirection::Rtl));
+    for symbol in ["A", "Q", "PR", "UD"] {
+        assert_eq!(language_direction(symbol), TextDirection::Rtl, "{}", symbol);
+    }
+    assert_eq!(language_direction("E"), TextDirection::Ltr);
+    assert_eq!(language_info(7).map(|language| language.symbol), Some("J"));
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "native")]
+
+mod common;
+
+use common::JwpubBuilder;
+use jw_parser::{parse_jwpub, parse_jwpub_dir, ParseOptions};
+
+#[test]
+fn output_dir_holds_no_database_after_parsing() {
+    let input = tempfile::tempdir().unwrap();
+    let output = tempfile::tempdir().unwrap();
+    let jwpub = JwpubBuilder::new()
+        .document(1102023201, "Article", "<p id=\"p1\">Hello</p>")
+        .write_to(input.path());
+
+    let manifest = parse_jwpub(jwpub.as_path(), output.path(), &ParseOptions::default()).unwrap();
+    assert_eq!(manifest.documents.len(), 1);
+
+    let databases: Vec<_> = walkdir::WalkDir::new(output.path())
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|extension| extension == "db"))
+        .collect();
+    assert!(databases.is_empty(), "found {:?}", databases);
+}
+
+#[test]
+fn batch_keeps_existing_publication_directories() {
+    let input = tempfile::tempdir().unwrap();
+    let output = tempfile::tempdir().unwrap();
+    JwpubBuilder::new()
+        .document(1102023201, "Article", "<p id=\"p1\">Hello</p>")
+        .write_to(input.path());
+    let earlier_run = output.path().join("w_2023_20230100");
+    std::fs::create_dir(&earlier_run).unwrap();
+    std::fs::write(earlier_run.join("notes.txt"), "keep me").unwrap();
+    let stale_staging = output.path().join(".partial-0");
+    std::fs::create_dir(&stale_staging).unwrap();
+    std::fs::write(stale_staging.join("leftover.jpg"), "stale").unwrap();
+
+    let items = parse_jwpub_dir(input.path(), output.path(), &ParseOptions::default()).unwrap();
+    let (dir, _) = items[0].result.as_ref().unwrap();
+
+    assert!(dir.ends_with("w_2023_20230100-2"), "{:?}", dir);
+    assert!(!dir.join("assets/leftover.jpg").exists() && !dir.join("leftover.jpg").exists());
+    assert_eq!(std::fs::read_to_string(earlier_run.join("notes.txt")).unwrap(), "keep me");
+    assert!(!stale_staging.exists());
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::JwpubBuilder;
+use jw_parser::parse_jwpub_bytes;
+
+/// Manifest as JSON, without the extraction timestamp that differs between runs
+fn manifest_json(data: &[u8]) -> serde_json::Value {
+    let (manifest, _) = parse_jwpub_bytes(data).unwrap();
+    let mut json = serde_json::to_value(&manifest).unwrap();
+    json.as_object_mut().unwrap().remove("extracted_at");
+    json
+}
+
+#[test]
+fn parallel_parse_matches_sequential_parse() {
+    let mut builder = JwpubBuilder::new();
+    for index in 0..48 {
+        let html = format!(
+            "<h1>Article {index}</h1><p id=\"p1\" data-pid=\"1\">Text {index} <a href=\"bible://NWTR/E/43003016\">John 3:16</a></p>"
+        );
+        builder = builder.document(1102023200 + index, &format!("Article {index}"), &html);
+    }
+    let data = builder.build();
+
+    let sequential = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap()
+        .install(|| manifest_json(&data));
+    let parallel = rayon::ThreadPoolBuilder::new()
+        .num_threads(8)
+        .build()
+        .unwrap()
+        .install(|| manifest_json(&data));
+
+    assert_eq!(sequential["documents"].as_array().unwrap().len(), 48);
+    assert_eq!(parallel, sequential);
+}
@@ -0,0 +1,27 @@
+#![cfg(feature = "native")]
+
+mod common;
+
+use common::JwpubBuilder;
+use jw_parser::{parse_jwpub, repackage_jwpub, ParseOptions};
+
+#[test]
+fn repackaged_documents_decrypt_to_the_edited_html() {
+    let dir = tempfile::tempdir().unwrap();
+    let original = JwpubBuilder::new()
+        .document(1102023201, "First", "<p id=\"p1\">Original one</p>")
+        .document(1102023202, "Second", "<p id=\"p1\">Original two</p>")
+        .write_to(dir.path());
+    let options = ParseOptions::builder().skip_assets(true).build();
+
+    let mut manifest = parse_jwpub(original.as_path(), dir.path(), &options).unwrap();
+    manifest.documents[0].html_raw = "<p id=\"p1\">Edited one</p>".to_string();
+    let edited = &manifest.documents[..1];
+
+    let repackaged = dir.path().join("edited.jwpub");
+    assert_eq!(repackage_jwpub(&original, edited, &repackaged).unwrap(), 1);
+
+    let reparsed = parse_jwpub(repackaged.as_path(), dir.path(), &options).unwrap();
+    let texts: Vec<_> = reparsed.documents.iter().map(|document| document.paragraphs[0].text.as_str()).collect();
+    assert_eq!(texts, ["Edited one", "Original two"]);
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "native")]
+
+mod common;
+
+use common::JwpubBuilder;
+use jw_parser::assets::MemoryAssetSink;
+use jw_parser::{parse_jwpub_with_sink, JwpubError, ParseOptions};
+
+fn with_corrupt_leading_documents() -> JwpubBuilder {
+    JwpubBuilder::new()
+        .corrupt_document(1102023201, "Broken 1")
+        .corrupt_document(1102023202, "Broken 2")
+        .corrupt_document(1102023203, "Broken 3")
+        .document(1102023204, "Readable", "<p id=\"p1\">Still here</p>")
+}
+
+#[test]
+fn corrupt_leading_documents_are_skipped() {
+    let root = tempfile::tempdir().unwrap();
+    let jwpub = with_corrupt_leading_documents().write_to(root.path());
+
+    let options = ParseOptions::builder().skip_failed(true).build();
+    let output = parse_jwpub_with_sink(&jwpub, &options, &mut MemoryAssetSink::default()).unwrap();
+
+    let failed: Vec<_> = output.manifest.errors.iter().map(|error| error.id).collect();
+    assert_eq!(failed, [1102023201, 1102023202, 1102023203]);
+    assert_eq!(output.manifest.documents.len(), 1);
+    assert_eq!(output.manifest.documents[0].paragraphs[0].text, "Still here");
+}
+
+#[test]
+fn key_check_samples_past_corrupt_leading_documents() {
+    let root = tempfile::tempdir().unwrap();
+    let jwpub = with_corrupt_leading_documents()
+        .document(1102023205, "Readable", "<p id=\"p1\">Also here</p>")
+        .document(1102023206, "Readable", "<p id=\"p1\">And here</p>")
+        .write_to(root.path());
+
+    let error = parse_jwpub_with_sink(&jwpub, &ParseOptions::default(), &mut MemoryAssetSink::default()).unwrap_err();
+    assert!(matches!(error, JwpubError::DecryptionFailed { .. }), "{}", error);
+}